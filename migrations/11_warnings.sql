@@ -0,0 +1,11 @@
+CREATE TABLE warnings (
+    id INTEGER PRIMARY KEY NOT NULL,
+    bot_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    cause TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
+
+alter table bots add warn_limit integer not null DEFAULT 3;
@@ -0,0 +1,16 @@
+CREATE TABLE ads (
+    id INTEGER PRIMARY KEY NOT NULL,
+    bot_id INTEGER NOT NULL,
+    channel INTEGER NOT NULL,
+    post INTEGER NOT NULL,
+    messages TEXT NOT NULL,
+    user_id INTEGER NOT NULL,
+    target INTEGER NOT NULL,
+    price INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    tags TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE,
+    UNIQUE(bot_id, channel, post) ON CONFLICT REPLACE
+);
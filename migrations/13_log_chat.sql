@@ -0,0 +1 @@
+alter table bots add log_chat integer null;
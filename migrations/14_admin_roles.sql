@@ -0,0 +1,6 @@
+alter table bot_admins add role integer not null default 1;
+
+-- владельцем становится первый добавленный админ бота, остальные остаются админами
+update bot_admins set role = 0 where rowid in (
+    select min(rowid) from bot_admins group by bot_id
+);
@@ -0,0 +1,7 @@
+CREATE TABLE blacklist (
+    user_id INTEGER PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    cause TEXT NOT NULL,
+    added_by INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE banned ADD COLUMN until INTEGER;
@@ -0,0 +1,8 @@
+CREATE TABLE questions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    bot_id INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    options TEXT NOT NULL,
+
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
@@ -0,0 +1,13 @@
+CREATE TABLE allowlist (
+    bot_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+
+    PRIMARY KEY(bot_id, user_id),
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
+-- авторы объявлений во всех барахолках, остаются авторами и после снятия объявления
+CREATE TABLE authors (
+    user_id INTEGER PRIMARY KEY NOT NULL
+);
+INSERT INTO authors (user_id) SELECT DISTINCT user_id FROM ads;
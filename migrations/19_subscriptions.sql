@@ -0,0 +1,16 @@
+CREATE TABLE subscriptions (
+    bot_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+
+    PRIMARY KEY(bot_id, user_id, tag),
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
+
+CREATE TABLE muted_notifications (
+    bot_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+
+    PRIMARY KEY(bot_id, user_id),
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+CREATE TABLE saved_searches (
+    bot_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    query TEXT NOT NULL,
+    paused INTEGER NOT NULL DEFAULT 0,
+
+    PRIMARY KEY(bot_id, user_id, id),
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
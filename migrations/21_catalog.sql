@@ -0,0 +1 @@
+alter table bots add catalog_message integer null;
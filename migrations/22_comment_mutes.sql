@@ -0,0 +1,10 @@
+-- thread = 0 и channel = 0 - уведомления выключены для всех объявлений
+CREATE TABLE comment_mutes (
+    bot_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    channel INTEGER NOT NULL,
+    thread INTEGER NOT NULL,
+
+    PRIMARY KEY(bot_id, user_id, channel, thread),
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
@@ -0,0 +1,9 @@
+CREATE TABLE favorites (
+    bot_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    channel INTEGER NOT NULL,
+    post INTEGER NOT NULL,
+
+    PRIMARY KEY(bot_id, user_id, channel, post),
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
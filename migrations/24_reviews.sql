@@ -0,0 +1,27 @@
+-- confirmed - покупатель подтвердил сделку, до этого оценки не принимаются
+CREATE TABLE deals (
+    bot_id INTEGER NOT NULL,
+    channel INTEGER NOT NULL,
+    post INTEGER NOT NULL,
+    seller INTEGER NOT NULL,
+    buyer INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    confirmed INTEGER NOT NULL DEFAULT 0,
+
+    PRIMARY KEY(bot_id, channel, post),
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
+
+CREATE TABLE reviews (
+    bot_id INTEGER NOT NULL,
+    channel INTEGER NOT NULL,
+    post INTEGER NOT NULL,
+    author INTEGER NOT NULL,
+    target INTEGER NOT NULL,
+    score INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+
+    PRIMARY KEY(bot_id, channel, post, author),
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
@@ -0,0 +1,9 @@
+CREATE TABLE routes (
+    id INTEGER PRIMARY KEY NOT NULL,
+    bot_id INTEGER NOT NULL,
+    channel INTEGER NOT NULL,
+    tag TEXT NULL,
+    target INTEGER NULL,
+
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+-- капчи по заявкам на вступление: после перезапуска бота такие заявки отклоняются
+CREATE TABLE join_captchas (
+    bot_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    message INTEGER NOT NULL,
+
+    PRIMARY KEY(bot_id, user_id),
+    FOREIGN KEY (bot_id) REFERENCES bots(id) ON DELETE CASCADE
+);
@@ -1,7 +1,10 @@
 use std::collections::HashSet;
 
+use teloxide::types::{ChatId, Message, UserId};
+
 use super::entity::{Content, Target};
 use super::Price;
+use crate::persistent::PublishedAd;
 
 #[derive(Debug, Clone)]
 pub struct Ad {
@@ -31,4 +34,16 @@ impl Ad {
             },
         }
     }
+    pub fn published(&self, channel: ChatId, user_id: UserId, msgs: &[Message]) -> PublishedAd {
+        PublishedAd {
+            channel,
+            messages: msgs.iter().map(|m|m.id.0).collect(),
+            user_id,
+            target: self.target.clone() as i64,
+            price: self.price as i64,
+            text: self.text.clone(),
+            tags: self.tags.iter().cloned().collect(),
+            created: chrono::Utc::now().timestamp(),
+        }
+    }
 }
\ No newline at end of file
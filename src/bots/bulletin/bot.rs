@@ -19,9 +19,13 @@ pub fn start(config: Config) -> ShutdownToken {
     let token = dispatcher.shutdown_token();
     tokio::spawn(async move {
 
-        let bot_username = bot.get_me().await.ok_or_log()
+        let me = bot.get_me().await.ok_or_log();
+        let bot_username = me.as_ref()
             .map(|me|me.username().to_owned())
             .unwrap_or("unknown".to_owned());
+        if let Some(me) = me {
+            config.set_me(me.id, bot_username.clone());
+        }
         let mut channel_name = "unknown".to_owned();
         let mut invite_link = None;
         if let Some(chat) = bot.get_chat(config.channel).await.ok_or_log() {
@@ -30,6 +34,11 @@ pub fn start(config: Config) -> ShutdownToken {
             }
             invite_link = chat.invite_link().map(|s|s.to_owned());
         }
+        for channel in config.channels() {
+            if let Some(chat) = bot.get_chat(channel).await.ok_or_log() {
+                config.set_channel_username(channel, chat.username().map(|s|s.to_owned()));
+            }
+        }
         config.sender.send(persistent::DBAction::SetInfo( persistent::BotInfo {
             username: bot_username.clone(), 
             channel_name,
@@ -40,6 +49,10 @@ pub fn start(config: Config) -> ShutdownToken {
             BotCommand::new("/help", "Помощь"), 
             BotCommand::new("/create", "Создать"), 
             BotCommand::new("/publish", "Опубликовать"), 
+            BotCommand::new("/subscriptions", "Подписки"),
+            BotCommand::new("/searches", "Сохраненные поиски"),
+            BotCommand::new("/favorites", "Избранное"),
+            BotCommand::new("/requests", "Заявки на вступление (для админов)"),
             ]).await;
         if let Err(e) = set_cmd {
             log::error!("Error on bot starting: {:?}", e);
@@ -48,10 +61,15 @@ pub fn start(config: Config) -> ShutdownToken {
         let mut listener = teloxide::dispatching::update_listeners::polling_default(bot.clone()).await;
         let stop_token = listener.stop_token();
         log::info!("Bot @{} started!", bot_username);
+        fsm::expire_stale_captchas(&bot, &config).await;
+        let digest = tokio::spawn(digest::schedule(bot.clone(), config.clone()));
+        let catalog = tokio::spawn(catalog::schedule(bot.clone(), config.clone()));
         dispatcher.dispatch_with_listener(
             listener, 
             Arc::new(StoppableErrorHandler(stop_token))
         ).await;
+        digest.abort();
+        catalog.abort();
     });
     token
 }
@@ -60,7 +78,7 @@ struct StoppableErrorHandler(StopToken);
 
 impl ErrorHandler<RequestError> for StoppableErrorHandler  {
     fn handle_error(self: Arc<Self>, error: RequestError) -> futures_util::future::BoxFuture<'static, ()> {
-        log::error!("{}", error.to_string());
+        log::error!("{}", error);
         if let RequestError::Api(teloxide::ApiError::NotFound) = error {
             self.0.stop();
             log::info!("Bot stopped");
@@ -0,0 +1,67 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+const OPTIONS_COUNT: usize = 4;
+const EMOJIS: [(&str, &str); 8] = [
+    ("🍎", "яблоко"),
+    ("🚗", "машину"),
+    ("🐱", "кошку"),
+    ("⚽", "мяч"),
+    ("🌲", "ёлку"),
+    ("🎁", "подарок"),
+    ("🔑", "ключ"),
+    ("☂️", "зонт"),
+];
+
+/// Проверка на бота: вопрос и варианты ответа для кнопок
+#[derive(Debug, Clone)]
+pub struct Captcha {
+    pub question: String,
+    pub options: Vec<String>,
+    pub answer: usize,
+}
+
+impl Captcha {
+    pub fn random() -> Self {
+        if random(2) == 0 {
+            Self::arithmetic()
+        } else {
+            Self::emoji()
+        }
+    }
+    fn arithmetic() -> Self {
+        let (a, b) = (random(9) + 2, random(9) + 2);
+        let answer = random(OPTIONS_COUNT);
+        //варианты идут подряд, правильный на случайном месте
+        let options = (0..OPTIONS_COUNT).map(|i|(a + b + i - answer).to_string()).collect();
+        Self { question: format!("Сколько будет {a} + {b}?"), options, answer }
+    }
+    fn emoji() -> Self {
+        let start = random(EMOJIS.len());
+        let answer = random(OPTIONS_COUNT);
+        let emojis: Vec<_> = (0..OPTIONS_COUNT).map(|i|EMOJIS[(start + i) % EMOJIS.len()]).collect();
+        Self {
+            question: format!("Нажми на {}", emojis[answer].1),
+            options: emojis.iter().map(|(emoji, _)|emoji.to_string()).collect(),
+            answer,
+        }
+    }
+}
+
+//без отдельной зависимости: RandomState инициализируется случайным ключом
+fn random(bound: usize) -> usize {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u128);
+    (hasher.finish() % bound as u64) as usize
+}
+
+#[test]
+fn test_arithmetic() {
+    for _ in 0..100 {
+        let captcha = Captcha::arithmetic();
+        let sum: usize = captcha.question.split(|c: char|!c.is_ascii_digit())
+            .filter_map(|n|n.parse::<usize>().ok())
+            .sum();
+        assert_eq!(sum.to_string(), captcha.options[captcha.answer]);
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use super::*;
+use crate::impls::LoggableErrorResult;
+use crate::persistent::PublishedAd;
+use teloxide::{ApiError, RequestError};
+use teloxide::types::ParseMode;
+use teloxide::utils::markdown::{bold, escape};
+
+//объявления старше этого срока считаются неактуальными и в каталог не попадают
+const CATALOG_AD_TTL: chrono::Duration = chrono::Duration::days(30);
+//каталог перестраивается и по таймеру, чтобы из него уходили устаревшие объявления
+const REFRESH_PERIOD: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+pub async fn schedule(bot: WBot, conf: Conf) {
+    if !conf.catalog() {
+        return
+    }
+    loop {
+        refresh(&bot, &conf).await;
+        tokio::time::sleep(REFRESH_PERIOD).await;
+    }
+}
+
+/// Перестраивает каталог в фоне после публикации или снятия объявления
+pub fn update(bot: &WBot, conf: &Conf) {
+    if conf.catalog() {
+        let (bot, conf) = (bot.clone(), conf.clone());
+        tokio::spawn(async move { refresh(&bot, &conf).await });
+    }
+}
+
+/// Редактирует закрепленный каталог, а если его нет - публикует и закрепляет новый
+async fn refresh(bot: &WBot, conf: &Conf) {
+    let mut last_text = conf.catalog_text.lock().await;
+    let text = make_catalog(conf);
+    if last_text.as_ref() == Some(&text) {
+        return
+    }
+    if let Some(message) = conf.catalog_message() {
+        let edited = bot.edit_message_text(conf.channel, message, &text)
+            .parse_mode(ParseMode::MarkdownV2)
+            .disable_web_page_preview(true).await;
+        match edited {
+            Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => {
+                *last_text = Some(text);
+                return
+            },
+            Err(RequestError::Api(ApiError::MessageToEditNotFound | ApiError::MessageIdInvalid)) => {
+                log::warn!("catalog message {} is gone, posting a new one", message);
+                // сообщение могло остаться закрепленным, даже если его больше нельзя найти
+                bot.unpin_chat_message(conf.channel).message_id(message).await.ok_or_log();
+            },
+            // временная ошибка: каталог обновится при следующем обновлении
+            Err(e) => {
+                log::error!("Err on edit catalog message {}: {:?}", message, e);
+                return
+            },
+        }
+    }
+    let sent = bot.send_message(conf.channel, &text)
+        .parse_mode(ParseMode::MarkdownV2)
+        .disable_web_page_preview(true)
+        .disable_notification(true).await;
+    if let Some(msg) = sent.ok_or_log() {
+        bot.pin_chat_message(conf.channel, msg.id).disable_notification(true).await.ok_or_log();
+        conf.set_catalog_message(Some(msg.id));
+        *last_text = Some(text);
+    }
+}
+
+/// Актуальные объявления по тегам, от новых к старым. Объявление с несколькими тегами попадает в каждый
+fn make_catalog(conf: &Config) -> String {
+    let ads = conf.ads_since((chrono::Utc::now() - CATALOG_AD_TTL).timestamp());
+    let mut untagged = Vec::new();
+    let groups = ads.iter().rev().fold(BTreeMap::new(), |mut map: BTreeMap<_, Vec<&PublishedAd>>, ad|{
+        if ad.tags.is_empty() {
+            untagged.push(ad);
+        }
+        for tag in &ad.tags {
+            map.entry(tag.clone()).or_default().push(ad);
+        }
+        map
+    });
+    let no_tag = conf.template(Template::NoTag).to_owned();
+    let mut text = bold(&escape(conf.template(Template::CatalogHeader)));
+    if ads.is_empty() {
+        return text + "\n\n" + &escape(conf.template(Template::CatalogEmpty))
+    }
+    let groups = groups.into_iter().chain((!untagged.is_empty()).then_some((no_tag, untagged)));
+    for (tag, ads) in groups {
+        let header = format!("\n\n{}", bold(&format!("\\#{}", escape(&tag))));
+        if text.len() + header.len() > impls::MAX_LIST_MESSAGE_LEN {
+            text += "\n\n…";
+            break
+        }
+        text += &header;
+        for line in ads.into_iter().filter_map(|ad|impls::ad_list_item(ad, conf)) {
+            if text.len() + line.len() > impls::MAX_LIST_MESSAGE_LEN {
+                return text + "\n…"
+            }
+            text += "\n";
+            text += &line;
+        }
+    }
+    text
+}
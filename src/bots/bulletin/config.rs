@@ -1,23 +1,128 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Mutex;
 use super::CONF;
 
 use strum::EnumCount;
-use teloxide::types::{UserId, ChatId, KeyboardButton, ReplyMarkup};
+use teloxide::types::{UserId, ChatId, KeyboardButton, ReplyMarkup, User, MessageId};
 use super::flags::*;
+use super::roles::{self, Role, Permissions};
+use super::entity::AdQuery;
+use super::ad::Ad;
 
-use crate::{persistent::DBAction, impls::LoggableErrorResult, persistent::{BulletinConfig, BanInfo}};
+use crate::{persistent::DBAction, impls::LoggableErrorResult, persistent::{BulletinConfig, BanInfo, Warning, PublishedAd, Blacklist, Question, Authors, SavedSearch, Deal, Review, Route}};
+
+//предупреждения старше этого срока не учитываются
+const WARN_PERIOD: chrono::Duration = chrono::Duration::days(30);
+//одинаковые комментарии считаются повтором в пределах этого срока
+const REPEAT_WINDOW: chrono::Duration = chrono::Duration::minutes(10);
+//решенные заявки хранятся, чтобы показывать решение на старых карточках
+const DECIDED_REQUEST_TTL: chrono::Duration = chrono::Duration::days(1);
+//повторная жалоба того же пользователя на то же объявление раньше этого срока игнорируется
+const REPORT_COOLDOWN: chrono::Duration = chrono::Duration::hours(1);
+//не больше NOTIFY_LIMIT уведомлений о новых объявлениях за NOTIFY_PERIOD одному пользователю
+const NOTIFY_PERIOD: chrono::Duration = chrono::Duration::hours(1);
+const NOTIFY_LIMIT: usize = 10;
+pub const MAX_SAVED_SEARCHES: usize = 10;
+//после стольких неверных ответов на капчу за CAPTCHA_LOCKOUT новая капча не выдается
+const CAPTCHA_ATTEMPTS: usize = 3;
+const CAPTCHA_LOCKOUT: chrono::Duration = chrono::Duration::hours(1);
+//сколько последних пользователей помнить для поиска по @username
+const KNOWN_USERS_LIMIT: usize = 10_000;
+
+/// Обсуждение объявления: канал и id обсуждения
+type Thread = (ChatId, i32);
+
+/// Для чего выдана капча
+#[derive(Clone, Debug)]
+pub enum CaptchaPurpose {
+    Join(ChatId),
+    Create,
+}
+
+/// Выданная пользователю капча
+#[derive(Clone, Debug)]
+pub struct PendingCaptcha {
+    pub answer: usize,
+    pub purpose: CaptchaPurpose,
+    pub message: MessageId,
+}
+
+/// Заявка на вступление и разосланные админам карточки
+#[derive(Clone, Debug)]
+pub struct JoinRequest {
+    pub text: String,
+    pub cards: Vec<(ChatId, MessageId)>,
+    pub created: i64,
+    /// Кто и как решил заявку
+    pub decision: Option<String>,
+}
+
+/// Нерассмотренная жалоба на объявление и разосланные админам карточки
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub text: String,
+    pub cards: Vec<(ChatId, MessageId)>,
+}
+
+/// Комментарии в обсуждении, еще не отправленные получателю уведомлением
+#[derive(Clone, Debug)]
+pub struct CommentBatch {
+    pub count: usize,
+    /// Текст уведомления об одном комментарии, если в пачке больше ничего не придет
+    pub template: Template,
+    /// Ссылка на последний комментарий
+    pub url: String,
+}
 
 pub struct Config {
     pub token: String, 
-    pub admins: Mutex<HashMap<UserId, String>>,
+    pub admins: Mutex<HashMap<UserId, (String, Role)>>,
     pub channel: ChatId,
     pub sender: crossbeam::channel::Sender<DBAction>,
     pub receiver: crossbeam::channel::Receiver<DBAction>,
     pub tags: Vec<String>,
+    pub log_chat: Option<ChatId>,
+    pub questions: Vec<Question>,
+    pub routes: Vec<Route>,
     flags: Flags,
     templates: [String; Template::COUNT],
     banned: Mutex<HashMap<UserId, BanInfo>>,
+    warnings: Mutex<HashMap<UserId, Vec<Warning>>>,
+    warn_limit: usize,
+    /// Ключ - канал и первое сообщение объявления: номера постов в разных каналах могут совпадать
+    ads: Mutex<HashMap<(ChatId, i32), PublishedAd>>,
+    blacklist: std::sync::Arc<Blacklist>,
+    authors: std::sync::Arc<Authors>,
+    allowlist: HashMap<UserId, String>,
+    /// username -> пользователь и время, когда его видели последний раз
+    known_users: Mutex<HashMap<String, (UserId, i64)>>,
+    join_requests: Mutex<HashMap<(UserId, ChatId), JoinRequest>>,
+    /// Ключ - канал и пост объявления
+    reports: Mutex<HashMap<(ChatId, i32), Report>>,
+    /// Когда пользователь последний раз жаловался на объявление
+    reporters: Mutex<HashMap<(UserId, ChatId, i32), i64>>,
+    recent_comments: Mutex<HashMap<UserId, Vec<(i64, String)>>>,
+    captchas: Mutex<HashMap<UserId, PendingCaptcha>>,
+    verified: Mutex<HashSet<UserId>>,
+    channel_usernames: Mutex<HashMap<ChatId, String>>,
+    subscriptions: Mutex<HashMap<UserId, HashSet<String>>>,
+    muted_notifications: Mutex<HashSet<UserId>>,
+    sent_notifications: Mutex<HashMap<UserId, Vec<i64>>>,
+    saved_searches: Mutex<HashMap<UserId, Vec<SavedSearch>>>,
+    catalog_message: Mutex<Option<i32>>,
+    /// Последний опубликованный текст каталога. Блокировка не дает обновлять каталог параллельно
+    pub catalog_text: tokio::sync::Mutex<Option<String>>,
+    /// `None` - все обсуждения
+    comment_mutes: Mutex<HashSet<(UserId, Option<Thread>)>>,
+    comment_batches: Mutex<HashMap<(UserId, ChatId, i32), CommentBatch>>,
+    favorites: Mutex<HashMap<UserId, Vec<(ChatId, i32)>>>,
+    /// id и username самого бота
+    me: Mutex<Option<(UserId, String)>>,
+    /// Капчи по заявкам, выданные до перезапуска: ответы на них потеряны
+    stale_captchas: Mutex<Vec<(UserId, ChatId, MessageId)>>,
+    captcha_failures: Mutex<HashMap<UserId, Vec<i64>>>,
+    deals: Mutex<HashMap<(ChatId, i32), Deal>>,
+    reviews: Mutex<Vec<Review>>,
 }
 
 impl Config {
@@ -25,50 +130,585 @@ impl Config {
         use super::res::*;
         use KeyboardButton as KB;
         let mut keyboard = vec![
-            vec![KB::new(CREATE), KB::new(PUBLISH)]
+            vec![KB::new(CREATE), KB::new(PUBLISH)],
+            vec![KB::new(SUBSCRIPTIONS), KB::new(SAVED_SEARCHES), KB::new(FAVORITES)],
         ];
-        if self.is_admin(&user_id) {
-            keyboard.push(vec![KB::new(BAN), KB::new(UNBAN)]);
+        if self.can(&user_id, roles::BAN) {
+            keyboard.push(vec![KB::new(WARN), KB::new(BAN), KB::new(UNBAN)]);
+        }
+        if self.can(&user_id, roles::REMOVE_ADS) {
+            keyboard.push(vec![KB::new(TAKEDOWN)]);
+        }
+        if self.can(&user_id, roles::MANAGE_ADMINS) {
             keyboard.push(vec![KB::new(ADD_ADMIN), KB::new(REMOVE_ADMIN)]);
         }
         ReplyMarkup::keyboard(keyboard)
     }
-    pub fn add_admin(&self, user_id: UserId, name: String) {
-        self.admins.lock().unwrap().insert(user_id, name.clone());
-        self.sender.send(DBAction::AddAdmin(user_id.0 as i64, name)).ok_or_log();
+    pub fn add_admin(&self, user_id: UserId, name: String, role: Role) {
+        self.admins.lock().unwrap().insert(user_id, (name.clone(), role));
+        self.sender.send(DBAction::AddAdmin(user_id.0 as i64, name, role)).ok_or_log();
     }
     pub fn remove_admin(&self, user_id: UserId) -> Option<String> {
         self.sender.send(DBAction::RemoveAdmin(user_id.0 as i64)).ok_or_log();
-        self.admins.lock().unwrap().remove(&user_id)
+        self.admins.lock().unwrap().remove(&user_id).map(|(name, _)|name)
     }
-    pub fn admins(&self) -> Vec<(UserId, String)> {
-        self.admins.lock().unwrap().iter().map(|(k,v)|(*k, v.clone())).collect()
+    pub fn admins(&self) -> Vec<(UserId, String, Role)> {
+        self.admins.lock().unwrap().iter().map(|(k,(name, role))|(*k, name.clone(), *role)).collect()
+    }
+    /// Админы, которым разрешено действие
+    pub fn admins_with(&self, permission: Permissions) -> Vec<UserId> {
+        self.admins().into_iter().filter(|(_, _, role)|role.can(permission)).map(|(id, ..)|id).collect()
     }
     pub fn ban(&self, user_id: UserId, info: BanInfo) {
         self.sender.send(DBAction::Ban { 
             id: user_id.0 as i64, 
             name: info.name.clone(), 
             cause: info.cause.clone(),
+            until: info.until,
         }).ok_or_log();
         self.banned.lock().unwrap().insert(user_id, info);
     }
+    /// Снимает бан вместе с накопленными предупреждениями
     pub fn unban(&self, user_id: UserId) {
         self.sender.send(DBAction::Unban(user_id.0 as i64)).ok_or_log();
         self.banned.lock().unwrap().remove(&user_id);
+        self.warnings.lock().unwrap().remove(&user_id);
+    }
+    /// Меняет срок бана и возвращает обновленную запись
+    pub fn extend_ban(&self, user_id: UserId, until: Option<i64>) -> Option<BanInfo> {
+        let mut banned = self.banned.lock().unwrap();
+        let info = banned.get_mut(&user_id)?;
+        info.until = until;
+        self.sender.send(DBAction::ExtendBan { id: user_id.0 as i64, until }).ok_or_log();
+        Some(info.clone())
     }
+    /// Действующие баны, отсортированные по имени
     pub fn banned_users(&self) -> Vec<(UserId, BanInfo)> {
-        self.banned.lock().unwrap().iter().map(|(k,v)|(k.clone(),v.clone())).collect()
+        let mut banned: Vec<_> = self.banned.lock().unwrap().iter()
+            .filter(|(_, info)|info.is_active())
+            .map(|(k,v)|(*k,v.clone()))
+            .collect();
+        banned.sort_by_key(|(id, info)|(info.name.to_lowercase(), *id));
+        banned
+    }
+    /// Ищет действующие баны по id, имени или причине
+    pub fn search_banned(&self, query: &str) -> Vec<(UserId, BanInfo)> {
+        let query = query.trim().to_lowercase();
+        self.banned_users().into_iter()
+            .filter(|(id, info)|{
+                query.is_empty()
+                || id.0.to_string() == query
+                || info.name.to_lowercase().contains(&query)
+                || info.cause.to_lowercase().contains(&query)
+            })
+            .collect()
     }
     pub fn is_banned(&self, user_id: &UserId) -> Option<BanInfo> {
-        self.banned.lock().unwrap().get(user_id).cloned()
+        self.banned.lock().unwrap().get(user_id).filter(|info|info.is_active()).cloned()
+            .or_else(||self.blacklist.get(user_id).map(|entry|BanInfo { name: entry.name, cause: entry.cause, until: None }))
     }
-    pub fn is_admin(&self, user_id: &UserId) -> bool {
-        if CONF.is_global_admin(*user_id) {
+    /// Записывает предупреждение и возвращает все действующие предупреждения пользователя
+    pub fn warn(&self, user_id: UserId, cause: String) -> Vec<Warning> {
+        let created = chrono::Utc::now().timestamp();
+        self.sender.send(DBAction::Warn { id: user_id.0 as i64, cause: cause.clone(), created }).ok_or_log();
+        let mut warnings = self.warnings.lock().unwrap();
+        let user_warnings = warnings.entry(user_id).or_default();
+        let since = (chrono::Utc::now() - WARN_PERIOD).timestamp();
+        user_warnings.retain(|w|w.created > since);
+        user_warnings.push(Warning { cause, created });
+        user_warnings.clone()
+    }
+    pub fn warn_limit(&self) -> usize {
+        self.warn_limit
+    }
+    pub fn add_ad(&self, ad: PublishedAd) {
+        self.sender.send(DBAction::AddAd(ad.clone())).ok_or_log();
+        self.ads.lock().unwrap().insert((ad.channel, ad.post()), ad);
+    }
+    pub fn remove_ad(&self, channel: ChatId, post: i32) -> Option<PublishedAd> {
+        let ad = self.ads.lock().unwrap().remove(&(channel, post))?;
+        self.sender.send(DBAction::RemoveAd { channel: channel.0, post }).ok_or_log();
+        Some(ad)
+    }
+    /// Канал для объявления: первое подходящее правило, иначе основной канал
+    pub fn destination(&self, ad: &Ad) -> ChatId {
+        let target = ad.target.clone() as i64;
+        self.routes.iter()
+            .find(|route|route.tag.as_ref().is_none_or(|tag|ad.tags.contains(tag)) && route.target.is_none_or(|t|t == target))
+            .map(|route|route.channel)
+            .unwrap_or(self.channel)
+    }
+    /// Все каналы бота: основной и каналы из правил
+    pub fn channels(&self) -> Vec<ChatId> {
+        let mut channels = vec![self.channel];
+        for route in &self.routes {
+            if !channels.contains(&route.channel) {
+                channels.push(route.channel);
+            }
+        }
+        channels
+    }
+    /// Последние опубликованные объявления, от новых к старым
+    pub fn recent_ads(&self, limit: usize) -> Vec<PublishedAd> {
+        let mut ads: Vec<_> = self.ads.lock().unwrap().values().cloned().collect();
+        ads.sort_by_key(|ad|std::cmp::Reverse(ad.created));
+        ads.truncate(limit);
+        ads
+    }
+    /// Объявления, опубликованные после `since`, от старых к новым
+    pub fn ads_since(&self, since: i64) -> Vec<PublishedAd> {
+        let mut ads: Vec<_> = self.ads.lock().unwrap().values().filter(|ad|ad.created >= since).cloned().collect();
+        ads.sort_by_key(|ad|ad.created);
+        ads
+    }
+    pub fn catalog_message(&self) -> Option<MessageId> {
+        self.catalog_message.lock().unwrap().map(MessageId)
+    }
+    pub fn set_catalog_message(&self, message: Option<MessageId>) {
+        let message = message.map(|m|m.0);
+        *self.catalog_message.lock().unwrap() = message;
+        self.sender.send(DBAction::SetCatalog(message)).ok_or_log();
+    }
+    /// Объявления, подходящие под запрос, от новых к старым
+    pub fn search_ads(&self, query: &AdQuery, limit: usize) -> Vec<PublishedAd> {
+        let mut ads: Vec<_> = self.ads.lock().unwrap().values().filter(|ad|query.matches(ad)).cloned().collect();
+        ads.sort_by_key(|ad|std::cmp::Reverse(ad.created));
+        ads.truncate(limit);
+        ads
+    }
+    /// Username канала, если он публичный. Заполняется при старте бота
+    pub fn set_channel_username(&self, channel: ChatId, username: Option<String>) {
+        let mut usernames = self.channel_usernames.lock().unwrap();
+        match username {
+            Some(username) => usernames.insert(channel, username),
+            None => usernames.remove(&channel),
+        };
+    }
+    pub fn set_me(&self, id: UserId, username: String) {
+        *self.me.lock().unwrap() = Some((id, username));
+    }
+    pub fn bot_username(&self) -> Option<String> {
+        self.me.lock().unwrap().as_ref().map(|(_, username)|username.clone())
+    }
+    pub fn bot_id(&self) -> Option<UserId> {
+        self.me.lock().unwrap().as_ref().map(|(id, _)|*id)
+    }
+    /// Ссылка на пост объявления в канале
+    pub fn post_url(&self, ad: &PublishedAd) -> Option<String> {
+        self.message_url(ad.channel, ad.post())
+    }
+    pub fn message_url(&self, channel: ChatId, post: i32) -> Option<String> {
+        let username = self.channel_usernames.lock().unwrap().get(&channel).cloned();
+        teloxide::types::Message::url_of(channel, username.as_deref(), MessageId(post))
+            .map(|u|u.to_string())
+    }
+    /// Ссылка на оценки пользователя в боте
+    pub fn profile_url(&self, user_id: UserId) -> Option<String> {
+        let username = self.bot_username()?;
+        Some(format!("https://t.me/{}?start={}{}", username, super::res::PROFILE_START, user_id))
+    }
+    /// Ссылка, добавляющая пост канала в избранное
+    pub fn favorite_url(&self, channel: ChatId, post: i32) -> Option<String> {
+        let username = self.bot_username()?;
+        Some(format!("https://t.me/{}?start={}{}_{}", username, super::res::FAVORITE_START, channel, post))
+    }
+    pub fn subscriptions(&self, user_id: UserId) -> HashSet<String> {
+        self.subscriptions.lock().unwrap().get(&user_id).cloned().unwrap_or_default()
+    }
+    /// Подписывает на тег или отписывает от него. Возвращает `true`, если подписка появилась
+    pub fn toggle_subscription(&self, user_id: UserId, tag: String) -> bool {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let tags = subscriptions.entry(user_id).or_default();
+        let id = user_id.0 as i64;
+        if tags.remove(&tag) {
+            self.sender.send(DBAction::Unsubscribe { id, tag }).ok_or_log();
+            false
+        } else {
+            tags.insert(tag.clone());
+            self.sender.send(DBAction::Subscribe { id, tag }).ok_or_log();
             true
+        }
+    }
+    /// Подписчики хотя бы одного из тегов
+    pub fn subscribers(&self, tags: &[String]) -> Vec<UserId> {
+        self.subscriptions.lock().unwrap().iter()
+            .filter(|(_, subscribed)|tags.iter().any(|tag|subscribed.contains(tag)))
+            .map(|(user_id, _)|*user_id)
+            .collect()
+    }
+    pub fn notifications_muted(&self, user_id: UserId) -> bool {
+        self.muted_notifications.lock().unwrap().contains(&user_id)
+    }
+    /// Выключает уведомления или включает обратно. Возвращает новое состояние
+    pub fn toggle_notifications(&self, user_id: UserId) -> bool {
+        let mut muted = self.muted_notifications.lock().unwrap();
+        let now_muted = !muted.remove(&user_id);
+        if now_muted {
+            muted.insert(user_id);
+        }
+        self.sender.send(DBAction::MuteNotifications { id: user_id.0 as i64, muted: now_muted }).ok_or_log();
+        now_muted
+    }
+    pub fn saved_searches(&self, user_id: UserId) -> Vec<SavedSearch> {
+        self.saved_searches.lock().unwrap().get(&user_id).cloned().unwrap_or_default()
+    }
+    /// Сохраняет поиск, если не превышен лимит
+    pub fn add_saved_search(&self, user_id: UserId, query: String) -> Option<SavedSearch> {
+        let mut searches = self.saved_searches.lock().unwrap();
+        let user_searches = searches.entry(user_id).or_default();
+        if user_searches.len() >= MAX_SAVED_SEARCHES {
+            return None
+        }
+        let id = user_searches.iter().map(|s|s.id).max().unwrap_or_default() + 1;
+        let search = SavedSearch { id, query, paused: false };
+        user_searches.push(search.clone());
+        self.sender.send(DBAction::AddSearch { id: user_id.0 as i64, search: search.clone() }).ok_or_log();
+        Some(search)
+    }
+    /// Приостанавливает поиск или возобновляет его
+    pub fn toggle_saved_search(&self, user_id: UserId, search_id: i64) {
+        let mut searches = self.saved_searches.lock().unwrap();
+        let search = searches.get_mut(&user_id).and_then(|s|s.iter_mut().find(|s|s.id == search_id));
+        if let Some(search) = search {
+            search.paused = !search.paused;
+            self.sender.send(DBAction::PauseSearch { id: user_id.0 as i64, search_id, paused: search.paused }).ok_or_log();
+        }
+    }
+    pub fn delete_saved_search(&self, user_id: UserId, search_id: i64) {
+        if let Some(searches) = self.saved_searches.lock().unwrap().get_mut(&user_id) {
+            searches.retain(|s|s.id != search_id);
+            self.sender.send(DBAction::DeleteSearch { id: user_id.0 as i64, search_id }).ok_or_log();
+        }
+    }
+    /// Пользователи, у которых есть активный поиск, подходящий под объявление
+    pub fn search_subscribers(&self, ad: &PublishedAd) -> Vec<UserId> {
+        self.saved_searches.lock().unwrap().iter()
+            .filter(|(_, searches)|searches.iter().any(|s|!s.paused && AdQuery::parse(&s.query).matches(ad)))
+            .map(|(user_id, _)|*user_id)
+            .collect()
+    }
+    /// Выключены ли уведомления о комментариях в обсуждении `thread` канала `channel`, отдельно или вообще все
+    pub fn comments_muted(&self, user_id: UserId, channel: ChatId, thread: i32) -> bool {
+        let mutes = self.comment_mutes.lock().unwrap();
+        mutes.contains(&(user_id, None)) || mutes.contains(&(user_id, Some((channel, thread))))
+    }
+    /// `thread: None` - все обсуждения
+    pub fn comments_muted_exactly(&self, user_id: UserId, thread: Option<Thread>) -> bool {
+        self.comment_mutes.lock().unwrap().contains(&(user_id, thread))
+    }
+    /// Выключает уведомления о комментариях или включает обратно. Возвращает новое состояние
+    pub fn toggle_comments(&self, user_id: UserId, thread: Option<Thread>) -> bool {
+        let mut mutes = self.comment_mutes.lock().unwrap();
+        let muted = !mutes.remove(&(user_id, thread));
+        if muted {
+            mutes.insert((user_id, thread));
+        }
+        let thread = thread.map(|(channel, thread)|(channel.0, thread));
+        self.sender.send(DBAction::MuteComments { id: user_id.0 as i64, thread, muted }).ok_or_log();
+        muted
+    }
+    /// Добавляет комментарий в пачку. Возвращает `true`, если пачка только началась
+    pub fn add_to_comment_batch(&self, user_id: UserId, channel: ChatId, thread: i32, url: &str, template: Template) -> bool {
+        let mut batches = self.comment_batches.lock().unwrap();
+        match batches.get_mut(&(user_id, channel, thread)) {
+            Some(batch) => {
+                batch.count += 1;
+                batch.url = url.to_owned();
+                false
+            },
+            None => {
+                batches.insert((user_id, channel, thread), CommentBatch { count: 1, template, url: url.to_owned() });
+                true
+            },
+        }
+    }
+    pub fn take_comment_batch(&self, user_id: UserId, channel: ChatId, thread: i32) -> Option<CommentBatch> {
+        self.comment_batches.lock().unwrap().remove(&(user_id, channel, thread))
+    }
+    /// Избранные объявления пользователя, которые еще опубликованы
+    pub fn favorites(&self, user_id: UserId) -> Vec<PublishedAd> {
+        let posts = self.favorites.lock().unwrap().get(&user_id).cloned().unwrap_or_default();
+        let ads = self.ads.lock().unwrap();
+        posts.iter().filter_map(|key|ads.get(key).cloned()).collect()
+    }
+    /// Возвращает `false`, если объявление уже в избранном
+    pub fn add_favorite(&self, user_id: UserId, channel: ChatId, post: i32) -> bool {
+        let mut favorites = self.favorites.lock().unwrap();
+        let posts = favorites.entry(user_id).or_default();
+        if posts.contains(&(channel, post)) {
+            return false
+        }
+        posts.push((channel, post));
+        self.sender.send(DBAction::AddFavorite { id: user_id.0 as i64, channel: channel.0, post }).ok_or_log();
+        true
+    }
+    pub fn remove_favorite(&self, user_id: UserId, channel: ChatId, post: i32) {
+        if let Some(posts) = self.favorites.lock().unwrap().get_mut(&user_id) {
+            posts.retain(|p|*p != (channel, post));
+            self.sender.send(DBAction::RemoveFavorite { id: user_id.0 as i64, channel: channel.0, post }).ok_or_log();
+        }
+    }
+    /// Пользователи, у которых объявление в избранном
+    pub fn favorited_by(&self, channel: ChatId, post: i32) -> Vec<UserId> {
+        self.favorites.lock().unwrap().iter()
+            .filter(|(_, posts)|posts.contains(&(channel, post)))
+            .map(|(user_id, _)|*user_id)
+            .collect()
+    }
+    /// Убирает объявление из избранного у всех и возвращает, у кого оно было
+    pub fn take_favorited_by(&self, channel: ChatId, post: i32) -> Vec<UserId> {
+        let mut favorites = self.favorites.lock().unwrap();
+        let users: Vec<_> = favorites.iter_mut()
+            .filter_map(|(user_id, posts)|{
+                let len = posts.len();
+                posts.retain(|p|*p != (channel, post));
+                (posts.len() != len).then_some(*user_id)
+            })
+            .collect();
+        if !users.is_empty() {
+            self.sender.send(DBAction::ClearFavorites { channel: channel.0, post }).ok_or_log();
+        }
+        users
+    }
+    pub fn add_deal(&self, deal: Deal) {
+        self.sender.send(DBAction::AddDeal(deal.clone())).ok_or_log();
+        self.deals.lock().unwrap().insert((deal.channel, deal.post), deal);
+    }
+    pub fn deal(&self, channel: ChatId, post: i32) -> Option<Deal> {
+        self.deals.lock().unwrap().get(&(channel, post)).cloned()
+    }
+    /// Подтверждает сделку от имени покупателя. Возвращает сделку, если она ждала именно его
+    pub fn confirm_deal(&self, channel: ChatId, post: i32, buyer: UserId) -> Option<Deal> {
+        let mut deals = self.deals.lock().unwrap();
+        let deal = deals.get_mut(&(channel, post)).filter(|deal|deal.buyer == buyer && !deal.confirmed)?;
+        deal.confirmed = true;
+        self.sender.send(DBAction::ConfirmDeal { channel: channel.0, post }).ok_or_log();
+        Some(deal.clone())
+    }
+    /// Удаляет неподтвержденную сделку по отказу покупателя
+    pub fn reject_deal(&self, channel: ChatId, post: i32, buyer: UserId) -> Option<Deal> {
+        let mut deals = self.deals.lock().unwrap();
+        deals.get(&(channel, post)).filter(|deal|deal.buyer == buyer && !deal.confirmed)?;
+        self.sender.send(DBAction::RemoveDeal { channel: channel.0, post }).ok_or_log();
+        deals.remove(&(channel, post))
+    }
+    pub fn has_review(&self, channel: ChatId, post: i32, author: UserId) -> bool {
+        self.reviews.lock().unwrap().iter().any(|r|r.channel == channel && r.post == post && r.author == author)
+    }
+    /// Возвращает `false`, если автор уже оценил эту сделку
+    pub fn add_review(&self, review: Review) -> bool {
+        let mut reviews = self.reviews.lock().unwrap();
+        if reviews.iter().any(|r|r.channel == review.channel && r.post == review.post && r.author == review.author) {
+            return false
+        }
+        self.sender.send(DBAction::AddReview(review.clone())).ok_or_log();
+        reviews.push(review);
+        true
+    }
+    /// Отзывы о пользователе как о продавце в подтвержденных сделках, от старых к новым
+    fn seller_reviews(&self, user_id: UserId) -> Vec<Review> {
+        let deals = self.deals.lock().unwrap();
+        self.reviews.lock().unwrap().iter()
+            .filter(|r|r.target == user_id && deals.get(&(r.channel, r.post)).is_some_and(|deal|deal.confirmed && deal.seller == user_id))
+            .cloned().collect()
+    }
+    /// Средняя оценка пользователя как продавца и число оценок
+    pub fn rating(&self, user_id: UserId) -> Option<(f64, usize)> {
+        let scores: Vec<_> = self.seller_reviews(user_id).into_iter().map(|r|r.score).collect();
+        (!scores.is_empty()).then(||(scores.iter().sum::<i64>() as f64 / scores.len() as f64, scores.len()))
+    }
+    /// Последние отзывы о пользователе как о продавце, от новых к старым
+    pub fn reviews_of(&self, user_id: UserId, limit: usize) -> Vec<Review> {
+        self.seller_reviews(user_id).into_iter().rev().take(limit).collect()
+    }
+    /// Можно ли сейчас отправить пользователю уведомление: не выключены и не превышен лимит
+    pub fn take_notification(&self, user_id: UserId) -> bool {
+        if self.notifications_muted(user_id) {
+            return false
+        }
+        let now = chrono::Utc::now().timestamp();
+        let since = now - NOTIFY_PERIOD.num_seconds();
+        let mut sent = self.sent_notifications.lock().unwrap();
+        let user_sent = sent.entry(user_id).or_default();
+        user_sent.retain(|&t|t > since);
+        if user_sent.len() >= NOTIFY_LIMIT {
+            return false
+        }
+        user_sent.push(now);
+        true
+    }
+    /// Объявление пользователя по любому из его сообщений, в каком бы канале оно ни было
+    pub fn find_user_ad(&self, user_id: UserId, message_id: i32) -> Option<PublishedAd> {
+        self.ads.lock().unwrap().values().find(|ad|ad.user_id == user_id && ad.messages.contains(&message_id)).cloned()
+    }
+    /// Ищет объявление по любому из его сообщений в конкретном канале
+    pub fn find_channel_ad(&self, channel: ChatId, message_id: i32) -> Option<PublishedAd> {
+        self.ads.lock().unwrap().values().find(|ad|ad.channel == channel && ad.messages.contains(&message_id)).cloned()
+    }
+    /// Запоминает username пользователя, чтобы потом найти его по @username
+    pub fn remember_user(&self, user: &User) {
+        let Some(username) = &user.username else {
+            return
+        };
+        let username = username.to_lowercase();
+        let mut users = self.known_users.lock().unwrap();
+        if users.len() >= KNOWN_USERS_LIMIT && !users.contains_key(&username) {
+            let oldest = users.iter().min_by_key(|(_, (_, seen))|*seen).map(|(name, _)|name.clone());
+            if let Some(oldest) = oldest {
+                users.remove(&oldest);
+            }
+        }
+        users.insert(username, (user.id, chrono::Utc::now().timestamp()));
+    }
+    /// Запоминает комментарий и возвращает, сколько раз за последнее время пользователь прислал такой же текст
+    pub fn count_repeats(&self, user_id: UserId, text: &str) -> usize {
+        let now = chrono::Utc::now().timestamp();
+        let since = now - REPEAT_WINDOW.num_seconds();
+        let mut recent = self.recent_comments.lock().unwrap();
+        recent.retain(|_, comments|comments.iter().any(|(created, _)|*created > since));
+        let comments = recent.entry(user_id).or_default();
+        comments.retain(|(created, _)|*created > since);
+        comments.push((now, text.to_owned()));
+        comments.iter().filter(|(_, comment)|comment == text).count()
+    }
+    /// Капча нужна тем, кто еще не проходил ее и не публиковал объявлений
+    pub fn needs_captcha(&self, user_id: &UserId) -> bool {
+        self.captcha()
+            && !self.is_admin(user_id)
+            && !self.is_author(user_id)
+            && !self.verified.lock().unwrap().contains(user_id)
+    }
+    pub fn verify(&self, user_id: UserId) {
+        self.verified.lock().unwrap().insert(user_id);
+    }
+    /// Капча по заявке на вступление сохраняется, чтобы отклонить заявку, если бот перезапустится раньше ответа
+    pub fn add_captcha(&self, user_id: UserId, captcha: PendingCaptcha) {
+        if let CaptchaPurpose::Join(chat_id) = captcha.purpose {
+            self.sender.send(DBAction::AddJoinCaptcha { id: user_id.0 as i64, chat_id: chat_id.0, message: captcha.message.0 }).ok_or_log();
+        }
+        self.captchas.lock().unwrap().insert(user_id, captcha);
+    }
+    pub fn take_captcha(&self, user_id: UserId) -> Option<PendingCaptcha> {
+        let captcha = self.captchas.lock().unwrap().remove(&user_id)?;
+        if let CaptchaPurpose::Join(_) = captcha.purpose {
+            self.sender.send(DBAction::RemoveJoinCaptcha(user_id.0 as i64)).ok_or_log();
+        }
+        Some(captcha)
+    }
+    pub fn take_stale_captchas(&self) -> Vec<(UserId, ChatId, MessageId)> {
+        let stale = std::mem::take(&mut *self.stale_captchas.lock().unwrap());
+        for (user_id, ..) in &stale {
+            self.sender.send(DBAction::RemoveJoinCaptcha(user_id.0 as i64)).ok_or_log();
+        }
+        stale
+    }
+    /// Запоминает неверный ответ на капчу. true, если попытки исчерпаны
+    pub fn captcha_failed(&self, user_id: UserId) -> bool {
+        let now = chrono::Utc::now().timestamp();
+        let mut failures = self.captcha_failures.lock().unwrap();
+        let user_failures = failures.entry(user_id).or_default();
+        user_failures.retain(|failed|*failed > now - CAPTCHA_LOCKOUT.num_seconds());
+        user_failures.push(now);
+        user_failures.len() >= CAPTCHA_ATTEMPTS
+    }
+    pub fn captcha_locked(&self, user_id: &UserId) -> bool {
+        let since = chrono::Utc::now().timestamp() - CAPTCHA_LOCKOUT.num_seconds();
+        self.captcha_failures.lock().unwrap().get(user_id)
+            .is_some_and(|failures|failures.iter().filter(|failed|**failed > since).count() >= CAPTCHA_ATTEMPTS)
+    }
+    /// Снимает капчу по таймауту, если пользователь так и не ответил на нее
+    pub fn take_expired_captcha(&self, user_id: UserId, message: MessageId) -> Option<PendingCaptcha> {
+        if self.captchas.lock().unwrap().get(&user_id)?.message != message {
+            return None
+        }
+        self.take_captcha(user_id)
+    }
+    pub fn in_allowlist(&self, user_id: &UserId) -> bool {
+        self.allowlist.contains_key(user_id)
+    }
+    /// Публиковал ли пользователь объявления в любой из барахолок
+    pub fn is_author(&self, user_id: &UserId) -> bool {
+        self.authors.contains(user_id)
+    }
+    pub fn known_user(&self, username: &str) -> Option<UserId> {
+        self.known_users.lock().unwrap().get(&username.to_lowercase()).map(|(user_id, _)|*user_id)
+    }
+    pub fn add_join_request(&self, user_id: UserId, chat_id: ChatId, text: String, cards: Vec<(ChatId, MessageId)>) {
+        let now = chrono::Utc::now().timestamp();
+        let since = now - DECIDED_REQUEST_TTL.num_seconds();
+        let mut requests = self.join_requests.lock().unwrap();
+        requests.retain(|_, request|request.decision.is_none() || request.created > since);
+        requests.insert((user_id, chat_id), JoinRequest { text, cards, created: now, decision: None });
+    }
+    pub fn add_join_card(&self, user_id: UserId, chat_id: ChatId, card: (ChatId, MessageId)) {
+        if let Some(request) = self.join_requests.lock().unwrap().get_mut(&(user_id, chat_id)) {
+            request.cards.push(card);
+        }
+    }
+    pub fn join_request(&self, user_id: UserId, chat_id: ChatId) -> Option<JoinRequest> {
+        self.join_requests.lock().unwrap().get(&(user_id, chat_id)).cloned()
+    }
+    /// Записывает решение, если заявка еще ждет его. Возвращает заявку в состоянии до решения
+    pub fn decide_join_request(&self, user_id: UserId, chat_id: ChatId, decision: String) -> Option<JoinRequest> {
+        let mut requests = self.join_requests.lock().unwrap();
+        let request = requests.get_mut(&(user_id, chat_id))?;
+        let previous = request.clone();
+        if request.decision.is_none() {
+            request.decision = Some(decision);
+        }
+        Some(previous)
+    }
+    /// Заявки, ожидающие решения, от старых к новым
+    pub fn pending_join_requests(&self) -> Vec<(UserId, ChatId, JoinRequest)> {
+        let mut pending: Vec<_> = self.join_requests.lock().unwrap().iter()
+            .filter(|(_, request)|request.decision.is_none())
+            .map(|((user_id, chat_id), request)|(*user_id, *chat_id, request.clone()))
+            .collect();
+        pending.sort_by_key(|(.., request)|request.created);
+        pending
+    }
+    /// Регистрирует жалобу. false, если этот пользователь недавно уже жаловался
+    /// на объявление или жалоба на него еще не рассмотрена
+    pub fn open_report(&self, reporter: UserId, channel: ChatId, post: i32, text: String) -> bool {
+        let now = chrono::Utc::now().timestamp();
+        let since = now - REPORT_COOLDOWN.num_seconds();
+        let mut reporters = self.reporters.lock().unwrap();
+        reporters.retain(|_, reported|*reported > since);
+        if reporters.insert((reporter, channel, post), now).is_some() {
+            return false
+        }
+        let mut reports = self.reports.lock().unwrap();
+        if reports.contains_key(&(channel, post)) {
+            return false
+        }
+        reports.insert((channel, post), Report { text, cards: vec![] });
+        true
+    }
+    pub fn add_report_card(&self, channel: ChatId, post: i32, card: (ChatId, MessageId)) {
+        if let Some(report) = self.reports.lock().unwrap().get_mut(&(channel, post)) {
+            report.cards.push(card);
+        }
+    }
+    pub fn is_report_open(&self, channel: ChatId, post: i32) -> bool {
+        self.reports.lock().unwrap().contains_key(&(channel, post))
+    }
+    /// Закрывает жалобу. `None`, если ее уже рассмотрели
+    pub fn close_report(&self, channel: ChatId, post: i32) -> Option<Report> {
+        self.reports.lock().unwrap().remove(&(channel, post))
+    }
+    pub fn role(&self, user_id: &UserId) -> Option<Role> {
+        if CONF.is_global_admin(*user_id) {
+            Some(Role::Owner)
         } else {
-            self.admins.lock().unwrap().contains_key(user_id)
+            self.admins.lock().unwrap().get(user_id).map(|(_, role)|*role)
         }
     }
+    pub fn is_admin(&self, user_id: &UserId) -> bool {
+        self.role(user_id).is_some()
+    }
+    pub fn can(&self, user_id: &UserId, permission: Permissions) -> bool {
+        self.role(user_id).map(|role|role.can(permission)).unwrap_or(false)
+    }
     pub fn template(&self, template: Template) -> &str {
         self.templates[template as usize].as_str()
     }
@@ -81,14 +721,77 @@ impl Config {
     pub fn donate_enabled(&self) -> bool {
         !self.flags.check_flag(WITHOUT_DONATE)
     }
+    pub fn warn_ban(&self) -> bool {
+        self.flags.check_flag(WARN_BAN)
+    }
+    pub fn auto_approve_username(&self) -> bool {
+        self.flags.check_flag(AUTO_APPROVE_USERNAME)
+    }
+    pub fn auto_approve_authors(&self) -> bool {
+        self.flags.check_flag(AUTO_APPROVE_AUTHORS)
+    }
+    pub fn auto_approve_allowlist(&self) -> bool {
+        self.flags.check_flag(AUTO_APPROVE_ALLOWLIST)
+    }
+    pub fn auto_decline_banned(&self) -> bool {
+        self.flags.check_flag(AUTO_DECLINE_BANNED)
+    }
+    pub fn spam_links(&self) -> bool {
+        self.flags.check_flag(SPAM_LINKS)
+    }
+    pub fn spam_invites(&self) -> bool {
+        self.flags.check_flag(SPAM_INVITES)
+    }
+    pub fn spam_forwards(&self) -> bool {
+        self.flags.check_flag(SPAM_FORWARDS)
+    }
+    pub fn spam_mentions(&self) -> bool {
+        self.flags.check_flag(SPAM_MENTIONS)
+    }
+    pub fn spam_repeats(&self) -> bool {
+        self.flags.check_flag(SPAM_REPEATS)
+    }
+    pub fn spam_mute(&self) -> bool {
+        self.flags.check_flag(SPAM_MUTE)
+    }
+    pub fn captcha(&self) -> bool {
+        self.flags.check_flag(CAPTCHA)
+    }
+    pub fn daily_digest(&self) -> bool {
+        self.flags.check_flag(DAILY_DIGEST)
+    }
+    pub fn weekly_digest(&self) -> bool {
+        self.flags.check_flag(WEEKLY_DIGEST)
+    }
+    pub fn catalog(&self) -> bool {
+        self.flags.check_flag(CATALOG)
+    }
 }
 
 impl From<BulletinConfig> for Config {
     fn from(cfg: BulletinConfig) -> Self {
-        let BulletinConfig {token, channel, admins, banned, templates, tags, flags} = cfg;
+        let BulletinConfig {token, channel, admins, banned, templates, tags, flags, warnings, warn_limit, ads, log_chat, blacklist, questions, allowlist, authors, subscriptions, muted_notifications, saved_searches, catalog_message, comment_mutes, favorites, deals, reviews, routes, join_captchas} = cfg;
         let (sender, receiver) = crossbeam::channel::unbounded();
-        let admins = admins.into_iter().collect();
+        let admins = admins.into_iter().map(|(id, name, role)|(id, (name, role))).collect();
         let banned = banned.into_iter().collect();
+        let since = (chrono::Utc::now() - WARN_PERIOD).timestamp();
+        let warnings = warnings.into_iter().filter(|(_, w)|w.created > since).fold(HashMap::new(), |mut map: HashMap<_, Vec<_>>, (user_id, warning)|{
+            map.entry(user_id).or_default().push(warning);
+            map
+        });
+        let ads = ads.into_iter().map(|ad|((ad.channel, ad.post()), ad)).collect();
+        let favorites = favorites.into_iter().fold(HashMap::new(), |mut map: HashMap<_, Vec<_>>, (user_id, channel, post)|{
+            map.entry(user_id).or_default().push((channel, post));
+            map
+        });
+        let saved_searches = saved_searches.into_iter().fold(HashMap::new(), |mut map: HashMap<_, Vec<_>>, (user_id, search)|{
+            map.entry(user_id).or_default().push(search);
+            map
+        });
+        let subscriptions = subscriptions.into_iter().fold(HashMap::new(), |mut map: HashMap<_, HashSet<_>>, (user_id, tag)|{
+            map.entry(user_id).or_default().insert(tag);
+            map
+        });
         Self {
             token,
             channel,
@@ -96,14 +799,45 @@ impl From<BulletinConfig> for Config {
             receiver,
             admins: Mutex::new(admins),
             banned: Mutex::new(banned),
+            warnings: Mutex::new(warnings),
+            warn_limit,
+            ads: Mutex::new(ads),
+            blacklist,
+            authors,
+            allowlist: allowlist.into_iter().collect(),
+            known_users: Default::default(),
+            join_requests: Default::default(),
+            reports: Default::default(),
+            reporters: Default::default(),
+            recent_comments: Default::default(),
+            captchas: Default::default(),
+            verified: Default::default(),
+            channel_usernames: Default::default(),
+            subscriptions: Mutex::new(subscriptions),
+            muted_notifications: Mutex::new(muted_notifications.into_iter().collect()),
+            sent_notifications: Default::default(),
+            saved_searches: Mutex::new(saved_searches),
+            catalog_message: Mutex::new(catalog_message),
+            catalog_text: Default::default(),
+            comment_mutes: Mutex::new(comment_mutes.into_iter().collect()),
+            comment_batches: Default::default(),
+            favorites: Mutex::new(favorites),
+            me: Default::default(),
+            stale_captchas: Mutex::new(join_captchas.into_iter().map(|(user_id, chat_id, message)|(user_id, chat_id, MessageId(message))).collect()),
+            captcha_failures: Default::default(),
+            deals: Mutex::new(deals.into_iter().map(|deal|((deal.channel, deal.post), deal)).collect()),
+            reviews: Mutex::new(reviews),
             templates: Template::create(templates),
             tags,
+            log_chat,
+            questions,
+            routes,
             flags,
         }
     }
 }
 
-#[derive(PartialEq, Hash, Clone, Copy, strum_macros::EnumCount)]
+#[derive(PartialEq, Hash, Clone, Copy, Debug, strum_macros::EnumCount)]
 #[repr(usize)]
 pub enum Template {
     Help,
@@ -138,6 +872,65 @@ pub enum Template {
     JoinDeclined,
     BanCommand,
     RequestForwarded,
+    UnmuteCommand,
+    Muted,
+    Unmuted,
+    WarnCommand,
+    Warned,
+    ReportCommand,
+    ReportSent,
+    AdTakenDown,
+    AppealRequest,
+    AppealSent,
+    QuestionnaireInfo,
+    ChooseOption,
+    AnswerWithText,
+    CaptchaRequest,
+    CaptchaPassed,
+    CaptchaFailed,
+    CaptchaTimeout,
+    SubscriptionsInfo,
+    NewAdNotification,
+    SavedSearchesInfo,
+    SearchRequest,
+    SearchSaved,
+    EmptySearch,
+    TooManySearches,
+    DailyDigest,
+    WeeklyDigest,
+    NoTag,
+    CatalogHeader,
+    CatalogEmpty,
+    CommentsSummary,
+    NewReply,
+    FavoriteLink,
+    FavoriteAdded,
+    FavoriteNotFound,
+    FavoritesInfo,
+    FavoritesEmpty,
+    FavoriteRemoved,
+    SoldButton,
+    AdSold,
+    AskBuyer,
+    BuyerNotFound,
+    RateBuyer,
+    RateSeller,
+    ReviewRequest,
+    ReviewSaved,
+    AlreadyReviewed,
+    FavoriteSold,
+    NoReviews,
+    BuyerNotReachable,
+    AdNotFound,
+    MuteUsage,
+    BanTermOutOfRange,
+    ReportAlreadySent,
+    CaptchaLocked,
+    DealPending,
+    ConfirmDeal,
+    DealRejected,
+    DealDeclined,
+    FavoriteEdited,
 }
 
 impl Template {
@@ -152,7 +945,7 @@ impl Template {
     }
     fn default_templates() -> [String; Template::COUNT] {
         use Template::*;
-        let mut r: [String; Template::COUNT] = Default::default();
+        let mut r: [String; Template::COUNT] = std::array::from_fn(|_|String::new());
         r[Help as usize] = super::res::HELP.into();
         r[RequestPrice as usize]    = "Назови свою цену (число) в рублях".into();
         r[NotAPrice as usize]       = "Это не цена, нужно прислать число".into();
@@ -185,6 +978,65 @@ impl Template {
         r[JoinDeclined as usize]    = "Заявка отклонена".into();
         r[BanCommand as usize]      = "!ban".into();
         r[RequestForwarded as usize]= "Спасибо, скоро рассмотрим вашу заявку. Повторно отправлять не нужно.".into();
+        r[UnmuteCommand as usize]   = "!unmute".into();
+        r[Muted as usize]           = "Пользователь не сможет писать до".into();
+        r[Unmuted as usize]         = "Пользователь снова может писать".into();
+        r[WarnCommand as usize]     = "!warn".into();
+        r[Warned as usize]          = "Предупреждение".into();
+        r[ReportCommand as usize]   = "!report".into();
+        r[ReportSent as usize]      = "Жалоба отправлена админам".into();
+        r[AdTakenDown as usize]     = "Админы сняли твое объявление с публикации. Причина:".into();
+        r[AppealRequest as usize]   = "Напиши, почему объявление стоит вернуть. Сообщение будет переслано админам".into();
+        r[AppealSent as usize]      = "Обжалование отправлено админам".into();
+        r[QuestionnaireInfo as usize] = "Ответь на несколько вопросов, анкета будет передана админам".into();
+        r[ChooseOption as usize]    = "Выбери один из вариантов кнопкой".into();
+        r[AnswerWithText as usize]  = "Ответь текстом".into();
+        r[CaptchaRequest as usize]  = "Подтверди, что ты не бот. На ответ есть две минуты".into();
+        r[CaptchaPassed as usize]   = "Проверка пройдена".into();
+        r[CaptchaFailed as usize]   = "Неверный ответ".into();
+        r[CaptchaTimeout as usize]  = "Время на проверку вышло".into();
+        r[SubscriptionsInfo as usize] = "Выбери теги, по которым присылать новые объявления, и настрой уведомления".into();
+        r[NewAdNotification as usize] = "Новое объявление по подписке".into();
+        r[SavedSearchesInfo as usize] = "Сохраненные поиски. Пришлю уведомление, когда появится подходящее объявление. Нажми на поиск, чтобы приостановить или возобновить его".into();
+        r[SearchRequest as usize]   = "Пришли запрос, например: продам коляска до 10000. Понимаются слова, #теги, куплю/продам/вопрос/рекомендация и цена: 10000-50000, от 10000, <50000".into();
+        r[SearchSaved as usize]     = "Поиск сохранен".into();
+        r[EmptySearch as usize]     = "Под такой запрос подходит любое объявление, уточни его".into();
+        r[TooManySearches as usize] = "Слишком много сохраненных поисков, сначала удали ненужные".into();
+        r[DailyDigest as usize]     = "Объявления за сутки".into();
+        r[WeeklyDigest as usize]    = "Объявления за неделю".into();
+        r[NoTag as usize]           = "без тега".into();
+        r[CatalogHeader as usize]   = "Каталог актуальных объявлений".into();
+        r[CatalogEmpty as usize]    = "Пока нет актуальных объявлений".into();
+        r[CommentsSummary as usize] = "Новых комментариев:".into();
+        r[NewReply as usize]        = "Ответили на твой комментарий".into();
+        r[FavoriteLink as usize]    = "⭐ В избранное".into();
+        r[FavoriteAdded as usize]   = "Объявление добавлено в избранное".into();
+        r[FavoriteNotFound as usize] = "Объявление не найдено, возможно, его уже сняли с публикации".into();
+        r[FavoritesInfo as usize]   = "Избранные объявления:".into();
+        r[FavoritesEmpty as usize]  = "В избранном пусто. Чтобы добавить объявление, нажми «В избранное» под его текстом в канале".into();
+        r[FavoriteRemoved as usize] = "Объявление из избранного снято с публикации:".into();
+        r[SoldButton as usize]      = "Продано".into();
+        r[AdSold as usize]          = "Объявление закрыто как проданное".into();
+        r[AskBuyer as usize]        = "Кто купил? Перешли сообщение покупателя или пришли его id, чтобы вы могли оценить друг друга. @username подойдет, если покупатель уже писал боту или в обсуждение".into();
+        r[BuyerNotFound as usize]   = "Не удалось найти покупателя. По @username бот находит только тех, кто писал ему или в обсуждение после его перезапуска. Перешли сообщение покупателя или пришли id".into();
+        r[RateBuyer as usize]       = "Оцени покупателя от 1 до 5".into();
+        r[RateSeller as usize]      = "Оцени продавца от 1 до 5:".into();
+        r[ReviewRequest as usize]   = "Напиши короткий отзыв или пропусти".into();
+        r[ReviewSaved as usize]     = "Спасибо, оценка сохранена".into();
+        r[AlreadyReviewed as usize] = "Ты уже оценил эту сделку".into();
+        r[FavoriteSold as usize]    = "Объявление из избранного продано:".into();
+        r[NoReviews as usize]       = "Оценок пока нет".into();
+        r[BuyerNotReachable as usize] = "Не удалось написать покупателю: он еще не запускал бота. Без его подтверждения сделку оценить нельзя".into();
+        r[AdNotFound as usize]      = "Объявление не найдено, возможно, его уже сняли с публикации".into();
+        r[MuteUsage as usize]       = "30m, 3h, 2d или 1w — срок от 30 секунд до 366 дней, больший срок сокращается до 366 дней".into();
+        r[BanTermOutOfRange as usize] = "Срок бана должен быть от 30 секунд до 366 дней. Для бессрочного бана не указывай срок".into();
+        r[ReportAlreadySent as usize] = "Жалоба на это объявление уже у админов".into();
+        r[CaptchaLocked as usize]   = "Слишком много неверных ответов. Попробуй через час".into();
+        r[DealPending as usize]     = "Покупателю отправлен запрос. Оценить друг друга можно будет после того, как он подтвердит покупку".into();
+        r[ConfirmDeal as usize]     = "Продавец отметил тебя покупателем. Подтверди, что купил у него:".into();
+        r[DealRejected as usize]    = "Покупатель не подтвердил сделку".into();
+        r[DealDeclined as usize]    = "Сделка не засчитана".into();
+        r[FavoriteEdited as usize]  = "Объявление из избранного изменено:".into();
         r
     }
 }
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use super::*;
+use crate::impls::LoggableErrorResult;
+use crate::persistent::PublishedAd;
+use chrono::{DateTime, Datelike, Utc, Weekday};
+use teloxide::types::ParseMode;
+use teloxide::utils::markdown::{bold, escape, italic};
+
+//дайджесты выходят в 9:00 по Москве, еженедельный - по понедельникам
+const DIGEST_HOUR_UTC: u32 = 6;
+
+/// Публикует дайджесты в канал по расписанию. Задача живет, пока работает бот
+pub async fn schedule(bot: WBot, conf: Conf) {
+    if !conf.daily_digest() && !conf.weekly_digest() {
+        return
+    }
+    loop {
+        let now = Utc::now();
+        let next = next_digest_time(now);
+        tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+        if conf.daily_digest() {
+            send_digest(&bot, &conf, Template::DailyDigest, chrono::Duration::days(1)).await;
+        }
+        if conf.weekly_digest() && next.weekday() == Weekday::Mon {
+            send_digest(&bot, &conf, Template::WeeklyDigest, chrono::Duration::weeks(1)).await;
+        }
+    }
+}
+
+async fn send_digest(bot: &WBot, conf: &Conf, title: Template, period: chrono::Duration) {
+    let ads = conf.ads_since((Utc::now() - period).timestamp());
+    if let Some(text) = make_digest(conf, conf.template(title), &ads) {
+        bot.send_message(conf.channel, text).parse_mode(ParseMode::MarkdownV2)
+            .disable_web_page_preview(true).await.ok_or_log();
+    }
+}
+
+/// Текст дайджеста: объявления сгруппированы по цели и первому тегу
+fn make_digest(conf: &Config, title: &str, ads: &[PublishedAd]) -> Option<String> {
+    if ads.is_empty() {
+        return None
+    }
+    let no_tag = conf.template(Template::NoTag).to_owned();
+    let groups = ads.iter().fold(BTreeMap::new(), |mut map: BTreeMap<_, Vec<_>>, ad|{
+        let tag = ad.tags.first().cloned().unwrap_or(no_tag.clone());
+        map.entry(ad.target).or_default().push((tag, ad));
+        map
+    });
+    let mut text = bold(&escape(title));
+    for (target, mut ads) in groups {
+        ads.sort_by(|(a, _), (b, _)|a.cmp(b));
+        let header = format!("\n\n{}", bold(&format!("\\#{}", escape(target_hashtag(conf, target)))));
+        if text.len() + header.len() > impls::MAX_LIST_MESSAGE_LEN {
+            return Some(text + "\n\n…")
+        }
+        text += &header;
+        let mut current_tag = None;
+        for (tag, ad) in ads {
+            let mut lines = vec![];
+            if current_tag.as_ref() != Some(&tag) {
+                lines.push(italic(&escape(&tag)));
+                current_tag = Some(tag);
+            }
+            lines.extend(impls::ad_list_item(ad, conf));
+            // заголовок тега не должен остаться в конце без объявлений
+            let chunk = format!("\n{}", lines.join("\n"));
+            if text.len() + chunk.len() > impls::MAX_LIST_MESSAGE_LEN {
+                return Some(text + "\n…")
+            }
+            text += &chunk;
+        }
+    }
+    Some(text)
+}
+
+/// Та же метка, что и в самом объявлении: `#куплю`, `#продам`...
+fn target_hashtag(conf: &Config, target: i64) -> &str {
+    let template = match Target::from_index(target) {
+        Some(Target::Buy) => Template::BuyText,
+        Some(Target::Sell) => Template::SellText,
+        Some(Target::Ask) => Template::AskText,
+        _ => Template::RecommendText,
+    };
+    conf.template(template).split_whitespace().next().unwrap_or_default()
+}
+
+fn next_digest_time(now: DateTime<Utc>) -> DateTime<Utc> {
+    let today = now.date_naive().and_hms_opt(DIGEST_HOUR_UTC, 0, 0).unwrap_or_default().and_utc();
+    if today > now {
+        today
+    } else {
+        today + chrono::Duration::days(1)
+    }
+}
+
+#[test]
+fn test_next_digest_time() {
+    let at = |s: &str|s.parse::<DateTime<Utc>>().unwrap();
+    assert_eq!(at("2024-03-04T06:00:00Z"), next_digest_time(at("2024-03-04T05:59:00Z")));
+    assert_eq!(at("2024-03-05T06:00:00Z"), next_digest_time(at("2024-03-04T06:00:00Z")));
+    assert_eq!(at("2024-03-05T06:00:00Z"), next_digest_time(at("2024-03-04T23:00:00Z")));
+}
@@ -2,9 +2,11 @@ use super::*;
 use super::res::*;
 
 use serde::{Serialize, Deserialize};
+use super::roles::{self, Role, Permissions};
+use crate::persistent::PublishedAd;
 
 use teloxide::dispatching::dialogue::GetChatId;
-use teloxide::types::{UserId, Update, ChatId, UpdateKind, MessageKind, MediaKind, MediaText, MessageCommon, MessageId};
+use teloxide::types::{UserId, Update, ChatId, UpdateKind, MessageKind, MediaKind, MediaText, MessageCommon, MessageId, MessageEntityKind};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum CallbackResponse {
@@ -21,6 +23,34 @@ pub enum CallbackResponse {
     ApproveSubscribe(UserId, ChatId),
     DeclineSubscribe(UserId, ChatId),
     BanSubscribe(UserId, ChatId),
+    ReportDelete(ChatId, i32),
+    ReportBan(UserId, ChatId, i32),
+    ReportDismiss(ChatId, i32),
+    AdminRole(Role),
+    TakedownAd(ChatId, i32),
+    TakedownReason(usize),
+    Appeal(ChatId, i32),
+    BannedPage(usize),
+    BannedEntry(UserId, usize),
+    ExtendBan(UserId, Option<usize>),
+    Answer(usize),
+    Captcha(usize),
+    ToggleSubscription(String),
+    ToggleNotifications,
+    AddSearch,
+    ToggleSearch(i64),
+    DeleteSearch(i64),
+    ToggleThreadComments(ChatId, i32),
+    ToggleAllComments(Option<(ChatId, i32)>),
+    RemoveFavorite(ChatId, i32),
+    Sold(ChatId, i32),
+    SkipBuyer,
+    Rate(ChatId, i32, u8),
+    SkipReview,
+    /// Кнопка «В избранное» под постом в канале
+    Favorite,
+    ConfirmDeal(ChatId, i32),
+    RejectDeal(ChatId, i32),
 }
 
 impl CallbackMessage for CallbackResponse {}
@@ -46,8 +76,17 @@ enum Command {
     Publish,
     Ban,
     Unban,
+    Warn,
+    Takedown,
     AddAdmin,
     RemoveAdmin,
+    Requests,
+    Subscriptions,
+    SavedSearches,
+    Favorites,
+    /// Ссылка вида `fav<канал>_<пост>`
+    AddFavorite(ChatId, i32),
+    Profile(UserId),
 }
 
 #[derive(Clone, Debug)]
@@ -61,6 +100,30 @@ pub enum UserAction {
     Remove(Vec<i32>),
     AddTag(String, i32),
     RemoveTag(String, i32),
+    /// Канал и пост снятого объявления
+    Appeal(ChatId, i32),
+    Answer(usize),
+    Captcha(usize),
+    Subscriptions,
+    ToggleSubscription(String),
+    ToggleNotifications,
+    SavedSearches,
+    AddSearch,
+    ToggleSearch(i64),
+    DeleteSearch(i64),
+    ToggleThreadComments(ChatId, i32),
+    ToggleAllComments(Option<(ChatId, i32)>),
+    Favorites,
+    AddFavorite(ChatId, i32),
+    RemoveFavorite(ChatId, i32),
+    Profile(UserId),
+    Sold(ChatId, i32),
+    SkipBuyer,
+    Rate(ChatId, i32, u8),
+    SkipReview,
+    /// Покупатель подтверждает или отклоняет сделку по посту канала
+    ConfirmDeal(ChatId, i32),
+    RejectDeal(ChatId, i32),
 }
 
 #[derive(Clone, Debug)]
@@ -68,12 +131,38 @@ pub enum AdminAction {
     Ban,
     Unban,
     UserToUnban(UserId),
+    Warn,
     AddAdmin,
     RemoveAdmin,
     AdminToRemove(UserId),
     ApproveSubscribe(UserId, ChatId),
     DeclineSubscribe(UserId, ChatId),
     BanSubscribe(UserId, ChatId),
+    /// Канал и пост жалобы
+    ReportDelete(ChatId, i32),
+    ReportBan(UserId, ChatId, i32),
+    ReportDismiss(ChatId, i32),
+    AdminRole(Role),
+    Takedown,
+    TakedownAd(ChatId, i32),
+    TakedownReason(usize),
+    BannedPage(usize),
+    BannedEntry(UserId, usize),
+    ExtendBan(UserId, Option<usize>),
+    Requests,
+}
+
+impl AdminAction {
+    pub fn permission(&self) -> Permissions {
+        use AdminAction::*;
+        match self {
+            Ban | Unban | UserToUnban(_) | Warn | ReportBan(..)
+                | BannedPage(_) | BannedEntry(..) | ExtendBan(..) => roles::BAN,
+            AddAdmin | RemoveAdmin | AdminToRemove(_) | AdminRole(_) => roles::MANAGE_ADMINS,
+            ApproveSubscribe(..) | DeclineSubscribe(..) | BanSubscribe(..) | Requests => roles::APPROVE_JOINS,
+            ReportDelete(..) | ReportDismiss(..) | Takedown | TakedownAd(..) | TakedownReason(_) => roles::REMOVE_ADS,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -152,14 +241,29 @@ impl GetChatId for Signal {
 
 impl Command {
     fn from_str(s: &str, _conf: Conf) -> Option<Self> {
+        let start = s.strip_prefix("/start ");
+        let favorite = start.and_then(|arg|arg.strip_prefix(FAVORITE_START)).and_then(parse_favorite);
+        if let Some((channel, post)) = favorite {
+            return Some(Self::AddFavorite(channel, post))
+        }
+        let profile = start.and_then(|arg|arg.strip_prefix(PROFILE_START)).and_then(|id|id.parse().ok());
+        if let Some(user_id) = profile {
+            return Some(Self::Profile(UserId(user_id)))
+        }
         Some(match s {
             "/help" | "/start" => Self::Help,
             "/create" | CREATE => Self::Create,
             "/publish" | PUBLISH => Self::Publish,
             "/ban" | BAN => Self::Ban,
             "/unban" | UNBAN => Self::Unban,
+            "/warn" | WARN => Self::Warn,
+            TAKEDOWN => Self::Takedown,
             ADD_ADMIN => Self::AddAdmin,
             REMOVE_ADMIN => Self::RemoveAdmin,
+            "/requests" => Self::Requests,
+            "/subscriptions" | SUBSCRIPTIONS => Self::Subscriptions,
+            "/searches" | SAVED_SEARCHES => Self::SavedSearches,
+            "/favorites" | FAVORITES => Self::Favorites,
             _ => return None
         })
     }
@@ -171,11 +275,19 @@ impl Into<SignalKind> for Command {
         match self {
             Command::Ban => SK::AdminAction(AdminAction::Ban),
             Command::Unban => SK::AdminAction(AdminAction::Unban),
+            Command::Warn => SK::AdminAction(AdminAction::Warn),
+            Command::Takedown => SK::AdminAction(AdminAction::Takedown),
             Command::AddAdmin => SK::AdminAction(AdminAction::AddAdmin),
             Command::RemoveAdmin => SK::AdminAction(AdminAction::RemoveAdmin),
+            Command::Requests => SK::AdminAction(AdminAction::Requests),
             Command::Help => SK::UserAction(UserAction::Help),
             Command::Create => SK::UserAction(UserAction::Create),
             Command::Publish => SK::UserAction(UserAction::Publish),
+            Command::Subscriptions => SK::UserAction(UserAction::Subscriptions),
+            Command::SavedSearches => SK::UserAction(UserAction::SavedSearches),
+            Command::Favorites => SK::UserAction(UserAction::Favorites),
+            Command::AddFavorite(channel, post) => SK::UserAction(UserAction::AddFavorite(channel, post)),
+            Command::Profile(user_id) => SK::UserAction(UserAction::Profile(user_id)),
         }
     }
 }
@@ -198,6 +310,34 @@ impl Into<SignalKind> for CallbackResponse {
             ApproveSubscribe(id,chat_id) => SK::AdminAction(A::ApproveSubscribe(id, chat_id)),
             DeclineSubscribe(id, chat_id) => SK::AdminAction(A::DeclineSubscribe(id, chat_id)),
             BanSubscribe(id, chat_id) => SK::AdminAction(A::BanSubscribe(id, chat_id)),
+            ReportDelete(channel, post) => SK::AdminAction(A::ReportDelete(channel, post)),
+            ReportBan(id, channel, post) => SK::AdminAction(A::ReportBan(id, channel, post)),
+            ReportDismiss(channel, post) => SK::AdminAction(A::ReportDismiss(channel, post)),
+            AdminRole(role) => SK::AdminAction(A::AdminRole(role)),
+            TakedownAd(channel, post) => SK::AdminAction(A::TakedownAd(channel, post)),
+            TakedownReason(i) => SK::AdminAction(A::TakedownReason(i)),
+            Appeal(channel, post) => SK::UserAction(U::Appeal(channel, post)),
+            BannedPage(page) => SK::AdminAction(A::BannedPage(page)),
+            BannedEntry(id, page) => SK::AdminAction(A::BannedEntry(id, page)),
+            ExtendBan(id, extension) => SK::AdminAction(A::ExtendBan(id, extension)),
+            Answer(i) => SK::UserAction(U::Answer(i)),
+            Captcha(i) => SK::UserAction(U::Captcha(i)),
+            ToggleSubscription(tag) => SK::UserAction(U::ToggleSubscription(tag)),
+            ToggleNotifications => SK::UserAction(U::ToggleNotifications),
+            AddSearch => SK::UserAction(U::AddSearch),
+            ToggleSearch(id) => SK::UserAction(U::ToggleSearch(id)),
+            DeleteSearch(id) => SK::UserAction(U::DeleteSearch(id)),
+            ToggleThreadComments(channel, thread) => SK::UserAction(U::ToggleThreadComments(channel, thread)),
+            ToggleAllComments(thread) => SK::UserAction(U::ToggleAllComments(thread)),
+            RemoveFavorite(channel, post) => SK::UserAction(U::RemoveFavorite(channel, post)),
+            Sold(channel, post) => SK::UserAction(U::Sold(channel, post)),
+            SkipBuyer => SK::UserAction(U::SkipBuyer),
+            Rate(channel, post, score) => SK::UserAction(U::Rate(channel, post, score)),
+            SkipReview => SK::UserAction(U::SkipReview),
+            //кнопка бывает только под постом в канале, там ее обрабатывает on_channel_callback
+            Favorite => SK::UserAction(U::Help),
+            ConfirmDeal(channel, post) => SK::UserAction(U::ConfirmDeal(channel, post)),
+            RejectDeal(channel, post) => SK::UserAction(U::RejectDeal(channel, post)),
         }
     }
 }
@@ -250,13 +390,10 @@ pub fn invoke_author(content: &Content) -> Option<UserId> {
         Content::TextAndPhoto(text, _) => text,
         _ => None?,
     };
-    match text.entities.first()?.kind {
-        teloxide::types::MessageEntityKind::TextLink {ref url} => {
-            if let Some(user_id) = url.query().map(|q|q.parse().ok()).flatten() {
-                return Some(UserId(user_id));
-            }
+    if let teloxide::types::MessageEntityKind::TextLink {ref url} = text.entities.first()?.kind {
+        if let Some(user_id) = url.query().and_then(|q|q.parse().ok()) {
+            return Some(UserId(user_id));
         }
-        _ => {}
     }
     //легаси, через время удалить
     log::warn!("cannot invoke author: {:?}", text);
@@ -275,46 +412,98 @@ pub struct GroupMessage {
     pub author: UserId,
     pub kind: GroupMessageKind,
 }
+
+/// Признаки спама в комментарии
+#[derive(Clone, Debug, Default)]
+pub struct SpamSigns {
+    pub links: bool,
+    pub invite_links: bool,
+    pub forwarded_post: bool,
+    pub mentions: usize,
+    pub text: Option<String>,
+}
+
+impl SpamSigns {
+    pub fn from_message(msg: &Message) -> Self {
+        let entities = msg.parse_entities().or_else(||msg.parse_caption_entities()).unwrap_or_default();
+        let urls: Vec<_> = entities.iter().filter_map(|entity|match entity.kind() {
+            MessageEntityKind::Url => Some(entity.text().to_owned()),
+            MessageEntityKind::TextLink { url } => Some(url.to_string()),
+            _ => None,
+        }).collect();
+        let mentions = entities.iter()
+            .filter(|entity|matches!(entity.kind(), MessageEntityKind::Mention | MessageEntityKind::TextMention { .. }))
+            .count();
+        Self {
+            links: !urls.is_empty(),
+            invite_links: urls.iter().any(|url|is_invite_link(url)),
+            forwarded_post: !msg.is_automatic_forward() && msg.forward_from_chat().map(|chat|chat.is_channel()).unwrap_or(false),
+            mentions,
+            text: msg.text().or(msg.caption()).map(ToOwned::to_owned),
+        }
+    }
+}
 #[derive(Clone, Debug)]
 pub enum GroupMessageKind {
     Comment {thread: i32, replied_author: UserId},
-    Mute(UserId),
-    Ban(UserId),
+    /// Ответ на комментарий обычного пользователя в обсуждении объявления
+    Reply {thread: i32, replied_author: UserId},
+    Mute(UserId, Option<chrono::Duration>),
+    Unmute(UserId),
+    Ban(UserId, String),
+    Warn(UserId, String),
+    Report {author: UserId, post: i32, reason: String},
     Dumb,
+    /// `!mute` с неразборчивым сроком
+    MuteUsage,
 }
 
 impl GroupMessage {
-    pub fn from_update(u: Update, conf: Conf) -> Option<Self> {
-        match u.kind {
-            UpdateKind::Message(msg) => Self::from_message(msg, conf),
-            _ => None
-        }
-    }
     pub fn from_message(msg: Message, conf: Conf) -> Option<Self> {
         let url = msg.url()?.to_string();
         let chat_id = msg.chat.id;
         let id = msg.id;
+        let thread_id = msg.thread_id;
         if let MessageKind::Common(MessageCommon {from, reply_to_message, media_kind, sender_chat, ..}) = msg.kind {
             let sender_chat_id = sender_chat.map(|chat|chat.id);
             let author = from?.id;
             let kind = if let Some(reply_to_message) = reply_to_message {
                 let thread = reply_to_message.id.0;
+                let post = reply_to_message.forward_from_message_id();
                 let content = media_to_content(media_kind)?;
+                let text = content.text()?;
+                let command = |template| parse_command(text, conf.template(template));
                 if let MessageKind::Common(MessageCommon{from, media_kind, ..}) = reply_to_message.kind {
-                    let replied_author = from?.id;
+                    let replied = from?;
+                    let replied_author = replied.id;
                     if replied_author.is_telegram() { 
                         let replied_content = media_to_content(media_kind)?;
                         let replied_author = invoke_author(&replied_content)?;
-                        //TODO: надо что-то придумать с дублированием
-                        if content.text()?.to_lowercase() == conf.template(config::Template::BanCommand).to_lowercase(){
-                            GroupMessageKind::Ban(replied_author)
+                        if let Some(cause) = command(Template::BanCommand) {
+                            GroupMessageKind::Ban(replied_author, cause.to_owned())
+                        } else if let Some(cause) = command(Template::WarnCommand) {
+                            GroupMessageKind::Warn(replied_author, cause.to_owned())
+                        } else if let (Some(reason), Some(post)) = (command(Template::ReportCommand), post) {
+                            GroupMessageKind::Report { author: replied_author, post, reason: reason.to_owned() }
                         } else {
                             GroupMessageKind::Comment { thread, replied_author}
                         }
-                    } else if content.text()?.to_lowercase() == conf.template(config::Template::MuteCommand).to_lowercase() {
-                        GroupMessageKind::Mute(replied_author)
-                    } else if content.text()?.to_lowercase() == conf.template(config::Template::BanCommand).to_lowercase(){
-                        GroupMessageKind::Ban(replied_author)
+                    } else if let Some(args) = command(Template::MuteCommand) {
+                        if args.is_empty() {
+                            GroupMessageKind::Mute(replied_author, None)
+                        } else if let Some(duration) = parse_duration(args) {
+                            GroupMessageKind::Mute(replied_author, Some(duration))
+                        } else {
+                            GroupMessageKind::MuteUsage
+                        }
+                    } else if command(Template::UnmuteCommand).is_some() {
+                        GroupMessageKind::Unmute(replied_author)
+                    } else if let Some(cause) = command(Template::BanCommand) {
+                        GroupMessageKind::Ban(replied_author, cause.to_owned())
+                    } else if let Some(cause) = command(Template::WarnCommand) {
+                        GroupMessageKind::Warn(replied_author, cause.to_owned())
+                    } else if let (Some(thread), false) = (thread_id, replied.is_bot) {
+                        GroupMessageKind::Reply { thread, replied_author }
                     } else {
                         GroupMessageKind::Dumb
                     }
@@ -331,3 +520,237 @@ impl GroupMessage {
         }
     } 
 }
+
+/// Если текст начинается с команды (без учета регистра), возвращает ее аргументы
+pub fn parse_command<'a>(text: &'a str, command: &str) -> Option<&'a str> {
+    let text = text.trim();
+    let split = text.char_indices().nth(command.chars().count()).map(|(i, _)|i).unwrap_or(text.len());
+    let (head, tail) = text.split_at(split);
+    if head.to_lowercase() == command.to_lowercase() && (tail.is_empty() || tail.starts_with(char::is_whitespace)) {
+        Some(tail.trim())
+    } else {
+        None
+    }
+}
+
+/// Ограничения короче 30 секунд или длиннее 366 дней Telegram считает бессрочными
+pub const MIN_RESTRICTION: chrono::Duration = chrono::Duration::seconds(30);
+pub const MAX_RESTRICTION: chrono::Duration = chrono::Duration::days(366);
+
+/// Разбирает длительность вида `30m`, `3h`, `2d`, `1w` (или `30м`, `3ч`, `2д`, `1н`)
+pub fn parse_duration(s: &str) -> Option<chrono::Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char|!c.is_ascii_digit())?;
+    let (value, unit) = s.split_at(split);
+    let value: i64 = value.parse().ok()?;
+    match unit.trim().to_lowercase().as_str() {
+        "m" | "min" | "м" | "мин" => chrono::Duration::try_minutes(value),
+        "h" | "ч" => chrono::Duration::try_hours(value),
+        "d" | "д" => chrono::Duration::try_days(value),
+        "w" | "н" => chrono::Duration::try_weeks(value),
+        _ => None,
+    }
+}
+
+/// Срок бана, который Telegram посчитал бы бессрочным
+#[derive(Debug, PartialEq)]
+pub struct TermOutOfRange;
+
+/// Отделяет необязательный срок в начале причины бана: `7d спам`
+pub fn parse_ban_cause(text: &str) -> std::result::Result<(Option<chrono::Duration>, &str), TermOutOfRange> {
+    let text = text.trim();
+    let (term, cause) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    match parse_duration(term) {
+        Some(duration) if (MIN_RESTRICTION..=MAX_RESTRICTION).contains(&duration) => Ok((Some(duration), cause.trim())),
+        Some(_) => Err(TermOutOfRange),
+        // срок с допустимой единицей, но слишком большим числом
+        None if term.starts_with(|c: char|c.is_ascii_digit())
+            && parse_duration(&format!("1{}", term.trim_start_matches(|c: char|c.is_ascii_digit()))).is_some() => Err(TermOutOfRange),
+        None => Ok((None, text)),
+    }
+}
+
+/// Новое окончание бана после продления на `days` дней, не дальше `MAX_RESTRICTION` от `now`
+pub fn extend_ban_until(until: Option<i64>, days: i64, now: i64) -> i64 {
+    let from = until.unwrap_or_default().max(now);
+    (from + chrono::Duration::days(days).num_seconds()).min(now + MAX_RESTRICTION.num_seconds())
+}
+
+/// Ссылка-приглашение в чат: `t.me/+...`, `t.me/joinchat/...`
+pub fn is_invite_link(url: &str) -> bool {
+    let url = url.trim().to_lowercase();
+    let url = url.trim_start_matches("https://").trim_start_matches("http://").trim_start_matches("www.");
+    ["t.me/+", "t.me/joinchat/", "telegram.me/+", "telegram.me/joinchat/", "tg://join"].iter()
+        .any(|prefix|url.starts_with(prefix))
+}
+
+impl Target {
+    /// Обратное к `Target as i64`, как цель хранится в базе
+    pub fn from_index(index: i64) -> Option<Self> {
+        match index {
+            0 => Some(Target::Buy),
+            1 => Some(Target::Sell),
+            2 => Some(Target::Ask),
+            3 => Some(Target::Recommend),
+            _ => None,
+        }
+    }
+    pub fn has_price(&self) -> bool {
+        matches!(self, Target::Buy | Target::Sell)
+    }
+}
+
+/// Поисковый запрос по объявлениям: `#тег продам iphone 10000-50000`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct AdQuery {
+    pub words: Vec<String>,
+    pub tags: Vec<String>,
+    pub target: Option<Target>,
+    pub min_price: Option<i64>,
+    pub max_price: Option<i64>,
+}
+
+impl AdQuery {
+    pub fn parse(text: &str) -> Self {
+        let mut query = Self::default();
+        let mut tokens = text.split_whitespace().map(|t|t.to_lowercase()).peekable();
+        while let Some(token) = tokens.next() {
+            let number = |s: &str|s.replace(['_', '.', ','], "").parse::<i64>().ok();
+            if let Some(tag) = token.strip_prefix('#').filter(|t|!t.is_empty()) {
+                query.tags.push(tag.to_owned());
+            } else if let Some(target) = parse_target(&token) {
+                query.target = Some(target);
+            } else if let Some(price) = token.strip_prefix('<').and_then(number) {
+                query.max_price = Some(price);
+            } else if let Some(price) = token.strip_prefix('>').and_then(number) {
+                query.min_price = Some(price);
+            } else if let Some((min, max)) = token.split_once('-').and_then(|(a, b)|Some((number(a)?, number(b)?))) {
+                query.min_price = Some(min);
+                query.max_price = Some(max);
+            } else if let Some(price) = (token == "от").then(||tokens.peek().and_then(|t|number(t))).flatten() {
+                query.min_price = Some(price);
+                tokens.next();
+            } else if let Some(price) = (token == "до").then(||tokens.peek().and_then(|t|number(t))).flatten() {
+                query.max_price = Some(price);
+                tokens.next();
+            } else {
+                query.words.push(token);
+            }
+        }
+        query
+    }
+    /// Запрос без условий, под него подходит любое объявление
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+    pub fn matches(&self, ad: &PublishedAd) -> bool {
+        let text = ad.text.to_lowercase();
+        let tags: Vec<_> = ad.tags.iter().map(|t|t.to_lowercase()).collect();
+        let target = Target::from_index(ad.target);
+        let price_filtered = self.min_price.is_some() || self.max_price.is_some();
+        self.words.iter().all(|w|text.contains(w.as_str()) || tags.iter().any(|t|t.contains(w.as_str())))
+            && self.tags.iter().all(|tag|tags.contains(tag))
+            && self.target.as_ref().is_none_or(|t|target.as_ref() == Some(t))
+            && (!price_filtered || target.is_some_and(|t|t.has_price()))
+            && self.min_price.is_none_or(|min|ad.price >= min)
+            && self.max_price.is_none_or(|max|ad.price <= max)
+    }
+}
+
+fn parse_target(word: &str) -> Option<Target> {
+    match word {
+        "куплю" | "покупка" | "buy" => Some(Target::Buy),
+        "продам" | "продажа" | "sell" => Some(Target::Sell),
+        "вопрос" | "ask" => Some(Target::Ask),
+        "рекомендация" | "совет" | "recommend" => Some(Target::Recommend),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UserRef {
+    Id(UserId),
+    Username(String),
+}
+
+/// Разбирает ссылку на пользователя: числовой id, `@username` или `tg://user?id=`
+pub fn parse_user_ref(text: &str) -> Option<UserRef> {
+    let text = text.trim();
+    if let Some(id) = text.strip_prefix("tg://user?id=") {
+        id.parse().ok().map(|id|UserRef::Id(UserId(id)))
+    } else if let Some(username) = text.strip_prefix('@').or_else(||text.strip_prefix("https://t.me/")) {
+        let valid = !username.is_empty() && username.chars().all(|c|c.is_ascii_alphanumeric() || c == '_');
+        valid.then(||UserRef::Username(username.to_owned()))
+    } else {
+        text.parse().ok().map(|id|UserRef::Id(UserId(id)))
+    }
+}
+
+/// Разбирает `<канал>_<пост>` из ссылки на добавление в избранное
+fn parse_favorite(arg: &str) -> Option<(ChatId, i32)> {
+    let (channel, post) = arg.split_once('_')?;
+    Some((ChatId(channel.parse().ok()?), post.parse().ok()?))
+}
+
+#[test]
+fn test_parse_command() {
+    assert_eq!(Some(""), parse_command("!Mute", "!mute"));
+    assert_eq!(Some("3h"), parse_command("!mute  3h ", "!mute"));
+    assert_eq!(None, parse_command("!muted", "!mute"));
+    assert_eq!(Some("2д"), parse_command("Молчать 2д", "молчать"));
+    assert_eq!(Some(chrono::Duration::hours(3)), parse_duration("3h"));
+    assert_eq!(Some(chrono::Duration::weeks(1)), parse_duration("1 н"));
+    assert_eq!(Ok((Some(chrono::Duration::days(7)), "спам")), parse_ban_cause("7d спам"));
+    assert_eq!(Ok((None, "спам 7d")), parse_ban_cause(" спам 7d"));
+    assert_eq!(Err(TermOutOfRange), parse_ban_cause("400d спам"));
+    assert_eq!(Err(TermOutOfRange), parse_ban_cause("99999999999999w спам"));
+    assert_eq!(None, parse_duration("3"));
+    assert_eq!(None, parse_duration("h"));
+    assert_eq!(None, parse_duration("99999999999999w"));
+    let (now, day) = (1_700_000_000, chrono::Duration::days(1).num_seconds());
+    assert_eq!(now + 7 * day, extend_ban_until(None, 7, now));
+    assert_eq!(now + 40 * day, extend_ban_until(Some(now + 10 * day), 30, now));
+    assert_eq!(now + MAX_RESTRICTION.num_seconds(), extend_ban_until(Some(now + 350 * day), 30, now));
+    assert_eq!(Some((ChatId(-1001234), 42)), parse_favorite("-1001234_42"));
+    assert_eq!(None, parse_favorite("42"));
+    assert_eq!(None, parse_favorite("-1001234_"));
+}
+
+#[test]
+fn test_parse_user_ref() {
+    assert_eq!(Some(UserRef::Id(UserId(42))), parse_user_ref(" 42 "));
+    assert_eq!(Some(UserRef::Id(UserId(42))), parse_user_ref("tg://user?id=42"));
+    assert_eq!(Some(UserRef::Username("some_user".into())), parse_user_ref("@some_user"));
+    assert_eq!(Some(UserRef::Username("some_user".into())), parse_user_ref("https://t.me/some_user"));
+    assert_eq!(None, parse_user_ref("@"));
+    assert_eq!(None, parse_user_ref("просто текст"));
+}
+
+#[test]
+fn test_invite_link() {
+    assert!(is_invite_link("https://t.me/+AbCdEf123"));
+    assert!(is_invite_link("t.me/joinchat/AbCdEf123"));
+    assert!(!is_invite_link("https://t.me/some_channel"));
+    assert!(!is_invite_link("https://example.com/t.me/+abc"));
+}
+
+#[test]
+fn test_ad_query() {
+    let query = AdQuery::parse("Продам #Телефоны iPhone 10000-50000");
+    assert_eq!(Some(Target::Sell), query.target);
+    assert_eq!(vec!["телефоны".to_owned()], query.tags);
+    assert_eq!(vec!["iphone".to_owned()], query.words);
+    assert_eq!((Some(10000), Some(50000)), (query.min_price, query.max_price));
+    let query = AdQuery::parse("велосипед от 5000 до 7000");
+    assert_eq!(vec!["велосипед".to_owned()], query.words);
+    assert_eq!((Some(5000), Some(7000)), (query.min_price, query.max_price));
+    assert_eq!(Some(100), AdQuery::parse("<100").max_price);
+    let ad = PublishedAd {
+        channel: ChatId(-100), messages: vec![1], user_id: UserId(1), target: Target::Sell as i64,
+        price: 30000, text: "Продаю iPhone 12".into(), tags: vec!["Телефоны".into()], created: 0,
+    };
+    assert!(AdQuery::parse("продам #телефоны iphone 10000-50000").matches(&ad));
+    assert!(AdQuery::parse("").matches(&ad));
+    assert!(!AdQuery::parse("куплю iphone").matches(&ad));
+    assert!(!AdQuery::parse("iphone до 1000").matches(&ad));
+}
@@ -1,6 +1,9 @@
 use teloxide::handler;
 
 use crate::persistent::BanInfo;
+use config::JoinRequest;
+use crate::bots::roles::Role;
+use super::res::{TAKEDOWN_REASONS, BAN_EXTENSIONS};
 
 use super::*;
 
@@ -10,8 +13,13 @@ pub fn process_admin(handler: FSMHandler) -> FSMHandler {
     ).branch(
         dptree::filter_map(Signal::filter_content)
         .branch(handler![State::WaitForward].endpoint(on_wait_forward))
-        .branch(handler![State::WaitCause(user_id)].endpoint(on_wait_cause))
+        .branch(handler![State::WaitCause(user_id, report)].endpoint(on_wait_cause))
+        .branch(handler![State::WaitSelectBanned(query)].endpoint(on_banned_search))
         .branch(handler![State::WaitForwardForAdmin].endpoint(on_wait_forward_for_admin))
+        .branch(handler![State::WaitForwardForWarn].endpoint(on_wait_forward_for_warn))
+        .branch(handler![State::WaitWarnCause(user_id)].endpoint(on_wait_warn_cause))
+        .branch(handler![State::WaitForwardForTakedown].endpoint(on_wait_forward_for_takedown))
+        .branch(handler![State::WaitTakedownReason(author, channel, messages)].endpoint(on_wait_takedown_reason))
     )
 }
 
@@ -26,32 +34,70 @@ async fn on_action(
     use AdminAction::*;
     match action {
         Ban => {
-            bot.send_message(chat_id, "пересылай публикацию злодея").await?;
+            bot.send_message(chat_id, "пересылай публикацию злодея или присылай его id, ссылку tg://user?id= или @username, если он писал боту или в обсуждение").await?;
             dialogue.update(State::WaitForward).await?;
         },
         Unban => {
-            let mut markup = InlineKeyboardMarkup::default();
-            for (user_id, BanInfo {name, cause}) in conf.banned_users() {
-                let text = format!("{name} ({cause})");
-                let data = CallbackResponse::User(user_id).to_msg_text()?;
-                markup = markup.append_row(vec![InlineKeyboardButton::callback(text, data)]);
-            }
-            bot.send_message(dialogue.chat_id(), "Выбери, кого амнистировать").reply_markup(markup).await?;
-            dialogue.update(State::WaitSelectBanned).await?;
+            let (text, markup) = markup_banned(&conf, "", 0);
+            bot.send_message(dialogue.chat_id(), text).reply_markup(markup).await?;
+            dialogue.update(State::WaitSelectBanned(String::new())).await?;
+        },
+        BannedPage(page) => {
+            let query = match dialogue.get().await? {
+                Some(State::WaitSelectBanned(query)) => query,
+                _ => String::new(),
+            };
+            let (text, markup) = markup_banned(&conf, &query, page);
+            edit_callback_markup(bot, upd, &text, markup).await?;
+        },
+        BannedEntry(user_id, page) => {
+            match conf.banned_users().into_iter().find(|(id, _)|*id == user_id).map(|(_, info)|info) {
+                Some(info) => {
+                    let (text, markup) = markup_banned_entry(user_id, &info, page);
+                    edit_callback_markup(bot, upd, &text, markup).await?;
+                }
+                None => edit_callback_message(bot, upd, "Этот пользователь уже не забанен").await?,
+            }
+        },
+        ExtendBan(user_id, extension) => {
+            let Some(info) = conf.banned_users().into_iter().find(|(id, _)|*id == user_id).map(|(_, info)|info) else {
+                edit_callback_message(bot, upd, "Этот пользователь уже не забанен").await?;
+                return Ok(())
+            };
+            let until = match extension {
+                Some(i) => {
+                    let (_, days) = BAN_EXTENSIONS.get(i).ok_or(anyhow!("unknown ban extension {i}"))?;
+                    //дальше 366 дней Telegram посчитает бан бессрочным, а бот - истекшим
+                    Some(extend_ban_until(info.until, *days, chrono::Utc::now().timestamp()))
+                }
+                None => None,
+            };
+            let info = conf.extend_ban(user_id, until).ok_or(anyhow!("ban of {user_id} not found"))?;
+            ban_in_chats(&bot, &conf, user_id, info.until).await;
+            let term = ban_term(&info);
+            log_moderation(&bot, &conf, LogEntry::new(dialogue.user_id(), "Бан продлен").target(user_id).reason(term.as_str())).await;
+            let (text, markup) = markup_banned_entry(user_id, &info, 0);
+            edit_callback_markup(bot, upd, &text, markup).await?;
+        },
+        Warn => {
+            bot.send_message(chat_id, "пересылай публикацию нарушителя или присылай его id, ссылку tg://user?id= или @username, если он писал боту или в обсуждение").await?;
+            dialogue.update(State::WaitForwardForWarn).await?;
         },
         UserToUnban(user_id) => {
-            conf.unban(user_id);
+            unban_user(&bot, &conf, user_id).await;
+            log_moderation(&bot, &conf, LogEntry::new(dialogue.user_id(), "Разбан").target(user_id)).await;
             bot.send_message(dialogue.chat_id(), "Разбанен").await?;
             dialogue.exit().await?;
         },
         AddAdmin => {
-            bot.send_message(dialogue.chat_id(), "Пересылай сообщение от человека - сделаем его админом").await?;
+            bot.send_message(dialogue.chat_id(), "Пересылай сообщение от человека или присылай его id, ссылку tg://user?id= или @username, если он писал боту или в обсуждение, - сделаем его админом").await?;
             dialogue.update(State::WaitForwardForAdmin).await?;
         },
         RemoveAdmin => {
             let markup = InlineKeyboardMarkup::default().inline_keyboard(conf.admins().into_iter()
-                .map(|(id, name)|InlineKeyboardButton::callback(
-                    name, 
+                .filter(|(_, _, role)|*role != Role::Owner)
+                .map(|(id, name, role)|InlineKeyboardButton::callback(
+                    format!("{name} ({})", role.name()), 
                     CallbackResponse::AdminToRemove(id).to_msg_text().unwrap())
                 )
                 .map(|btn|vec![btn])
@@ -59,63 +105,342 @@ async fn on_action(
             bot.send_message(dialogue.chat_id(), "Выбери, кого разжаловать").reply_markup(markup).await?;
         }
         AdminToRemove(u) => {
-            if let Some(name) = conf.remove_admin(u) {
+            if conf.role(&u) == Some(Role::Owner) {
+                bot.send_message(dialogue.chat_id(), "Владельца разжаловать нельзя").await?;
+            } else if let Some(name) = conf.remove_admin(u) {
+                log_moderation(&bot, &conf, LogEntry::new(dialogue.user_id(), "Админ разжалован").target(u)).await;
                 bot.send_message(dialogue.chat_id(), format!("{name} больше не админ")).await?;
             }
         },
         ApproveSubscribe(user_id, subscription_chat) => {
+            if join_request_decided(&bot, &conf, &upd, user_id, subscription_chat).await? {
+                return Ok(())
+            }
+            if let Err(e) = bot.approve_chat_join_request(subscription_chat, user_id).await {
+                log::error!("Err on approve join request: {:?}", e);
+                bot.send_message(chat_id, JOIN_REQUEST_FAILED).await?;
+                return Ok(())
+            }
+            decide_join_request(&bot, &conf, upd, user_id, subscription_chat, "✅ Принята").await?;
             let chat_id = ChatId(user_id.0 as i64);
-            bot.approve_chat_join_request(subscription_chat, user_id).await?;
+            log_moderation(&bot, &conf, LogEntry::new(dialogue.user_id(), "Заявка на вступление принята").target(user_id)).await;
             bot.send_message(chat_id, conf.template(Template::JoinApproved)).await?;
-            update_request_message(bot, upd, true).await?;
         }
         DeclineSubscribe(user_id, subscription_chat) => {
+            if join_request_decided(&bot, &conf, &upd, user_id, subscription_chat).await? {
+                return Ok(())
+            }
+            if let Err(e) = bot.decline_chat_join_request(subscription_chat, user_id).await {
+                log::error!("Err on decline join request: {:?}", e);
+                bot.send_message(chat_id, JOIN_REQUEST_FAILED).await?;
+                return Ok(())
+            }
+            decide_join_request(&bot, &conf, upd, user_id, subscription_chat, "❌ Отклонена").await?;
             let chat_id = ChatId(user_id.0 as i64);
-            bot.decline_chat_join_request(subscription_chat, user_id).await?;
+            log_moderation(&bot, &conf, LogEntry::new(dialogue.user_id(), "Заявка на вступление отклонена").target(user_id)).await;
             bot.send_message(chat_id, conf.template(Template::JoinDeclined)).await?;
-            update_request_message(bot, upd, false).await?;
         }
         BanSubscribe(user_id, subscription_chat) => {
-
-            bot.decline_chat_join_request(subscription_chat, user_id).await?;
-            if let UpdateKind::CallbackQuery(q) = upd.kind {
-                let msg = q.message.ok_or(anyhow!["Cannot invoke message from callback query"])?;
-                bot.edit_message_text(msg.chat.id, msg.id, "Пиши причину бана").await?;
-                dialogue.update(State::WaitCause(user_id)).await?;
-            } else {
-                bail!("Expects callback query, but not")
+            if join_request_decided(&bot, &conf, &upd, user_id, subscription_chat).await? {
+                return Ok(())
+            }
+            // бан нужен, даже если заявка уже недействительна
+            let declined = bot.decline_chat_join_request(subscription_chat, user_id).await.ok_or_log().is_some();
+            let decision = if declined { "⛔ Отклонена с баном" } else { "⛔ Бан (заявка уже недействительна)" };
+            decide_join_request(&bot, &conf, upd, user_id, subscription_chat, decision).await?;
+            bot.send_message(chat_id, "Пиши причину бана").await?;
+            dialogue.update(State::WaitCause(user_id, None)).await?;
+        }
+        Requests => {
+            let pending = conf.pending_join_requests();
+            let text = if pending.is_empty() { "Нет заявок на рассмотрении" } else { "Заявки на рассмотрении:" };
+            bot.send_message(chat_id, format!("{text}\n\nСписок ведется с последнего перезапуска бота, более ранние заявки смотри в настройках чата")).await?;
+            for (user_id, subscription_chat, request) in pending {
+                let card = bot.send_message(chat_id, request.text).reply_markup(join_request_markup(user_id, subscription_chat)).await?;
+                conf.add_join_card(user_id, subscription_chat, (card.chat.id, card.id));
             }
         }
+        ReportDelete(channel, post) => {
+            if !conf.is_report_open(channel, post) {
+                edit_callback_message(bot, upd, "Жалобу уже рассмотрели").await?;
+                return Ok(())
+            }
+            let ad = conf.find_channel_ad(channel, post);
+            let ids = ad.as_ref().map(|ad|ad.messages.clone()).unwrap_or(vec![post]);
+            match delete_msgs(&bot, channel, ids).await {
+                Ok(_) => {
+                    if !resolve_report(&bot, &conf, upd, (channel, post), "🗑 Объявление удалено").await? {
+                        return Ok(())
+                    }
+                    let mut entry = LogEntry::new(dialogue.user_id(), "Объявление удалено по жалобе");
+                    if let Some(ad) = ad {
+                        conf.remove_ad(ad.channel, ad.post());
+                        on_ad_removed(&bot, &conf, &ad);
+                        entry = entry.target(ad.user_id);
+                    }
+                    log_moderation(&bot, &conf, entry).await;
+                },
+                Err(e) => {
+                    log::error!("Err on remove reported ad: {:?}", e);
+                    edit_callback_message(bot, upd, "Не удалось удалить объявление. Возможно, прошло более 48 часов").await?;
+                },
+            }
+        }
+        ReportBan(user_id, channel, post) => {
+            if !conf.is_report_open(channel, post) {
+                edit_callback_message(bot, upd, "Жалобу уже рассмотрели").await?;
+                return Ok(())
+            }
+            // жалоба закрывается, только когда бан применен
+            bot.send_message(chat_id, "Пиши причину бана").await?;
+            dialogue.update(State::WaitCause(user_id, Some((channel, post)))).await?;
+        }
+        AdminRole(role) => {
+            if let Some(State::WaitAdminRole(user_id, name)) = dialogue.get().await? {
+                if role == Role::Owner {
+                    bail!("Owner role cannot be granted from bulletin bot");
+                }
+                conf.add_admin(user_id, name.clone(), role);
+                log_moderation(&bot, &conf, LogEntry::new(dialogue.user_id(), "Новый админ").target(user_id).reason(role.name())).await;
+                edit_callback_message(bot, upd, &format!("Отлично! {name} теперь {}", role.name())).await?;
+                dialogue.exit().await?;
+            }
+        }
+        Takedown => {
+            let markup = InlineKeyboardMarkup::new(conf.recent_ads(10).into_iter().map(|ad|{
+                let mut caption: String = ad.text.chars().take(40).collect();
+                if caption.is_empty() {
+                    caption = "[без текста]".to_owned();
+                }
+                vec![InlineKeyboardButton::callback(caption, CallbackResponse::TakedownAd(ad.channel, ad.post()).to_msg_text().unwrap())]
+            }));
+            bot.send_message(chat_id, "Пересылай публикацию или выбери из последних").reply_markup(markup).await?;
+            dialogue.update(State::WaitForwardForTakedown).await?;
+        }
+        TakedownAd(channel, post) => {
+            let ad = conf.find_channel_ad(channel, post).ok_or(anyhow!("ad with post {post} not found in {channel}"))?;
+            request_takedown_reason(bot, dialogue, ad.user_id, ad.channel, ad.messages).await?;
+        }
+        TakedownReason(i) => {
+            if let Some(State::WaitTakedownReason(author, channel, messages)) = dialogue.get().await? {
+                let reason = TAKEDOWN_REASONS.get(i).ok_or(anyhow!("unknown takedown reason {i}"))?;
+                take_down(bot, conf, dialogue, author, channel, messages, reason.to_string()).await?;
+            }
+        }
+        ReportDismiss(channel, post) => {
+            if !resolve_report(&bot, &conf, upd, (channel, post), "❌ Жалоба отклонена").await? {
+                return Ok(())
+            }
+            log_moderation(&bot, &conf, LogEntry::new(dialogue.user_id(), "Жалоба отклонена")).await;
+        }
     }
     Ok(())
 }
 
-async fn update_request_message(bot: WBot, upd: Update, approved: bool) -> FSMResult {
-    let text = if approved { "Запрос принят" } else { "Запрос отклонен" };
-    if let UpdateKind::CallbackQuery(q) = upd.kind {
-        let msg = q.message.ok_or(anyhow!["Cannot invoke message from callback query"])?;
-        bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+const BANNED_PAGE_SIZE: usize = 10;
+
+/// Страница списка забаненных с учетом поискового запроса
+fn markup_banned(conf: &Conf, query: &str, page: usize) -> (String, InlineKeyboardMarkup) {
+    let banned = conf.search_banned(query);
+    let pages = banned.len().div_ceil(BANNED_PAGE_SIZE).max(1);
+    let page = page.min(pages - 1);
+    let mut markup = InlineKeyboardMarkup::new(banned.iter()
+        .skip(page * BANNED_PAGE_SIZE)
+        .take(BANNED_PAGE_SIZE)
+        .map(|(user_id, BanInfo {name, cause, ..})|{
+            let cause: String = cause.chars().take(30).collect();
+            vec![InlineKeyboardButton::callback(format!("{name} ({cause})"), CallbackResponse::BannedEntry(*user_id, page).to_msg_text().unwrap())]
+        })
+    );
+    if pages > 1 {
+        let button = |text: String, page: usize| InlineKeyboardButton::callback(text, CallbackResponse::BannedPage(page).to_msg_text().unwrap());
+        let mut row = vec![];
+        if page > 0 {
+            row.push(button("◀".to_owned(), page - 1));
+        }
+        row.push(button(format!("{}/{pages}", page + 1), page));
+        if page + 1 < pages {
+            row.push(button("▶".to_owned(), page + 1));
+        }
+        markup = markup.append_row(row);
+    }
+    let text = match (banned.is_empty(), query.is_empty()) {
+        (true, true) => "Забаненных нет".to_owned(),
+        (true, false) => format!("По запросу «{query}» никого нет. Пришли другой текст для поиска"),
+        (false, true) => format!("Забанено: {}. Выбери, кого посмотреть, или пришли текст для поиска по имени и причине", banned.len()),
+        (false, false) => format!("По запросу «{query}» найдено: {}. Выбери, кого посмотреть, или пришли другой текст для поиска", banned.len()),
+    };
+    (text, markup)
+}
+
+pub(super) fn ban_term(info: &BanInfo) -> String {
+    match info.until.and_then(|until|chrono::DateTime::from_timestamp(until, 0)) {
+        Some(until) => format!("до {}", until.format("%d.%m.%Y %H:%M (UTC)")),
+        None => "бессрочно".to_owned(),
+    }
+}
+
+/// Карточка забаненного с кнопками разбана и продления
+fn markup_banned_entry(user_id: UserId, info: &BanInfo, page: usize) -> (String, InlineKeyboardMarkup) {
+    let text = format!("{} [{user_id}]\nПричина: {}\nСрок: {}", info.name, info.cause, ban_term(info));
+    let mut markup = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Разбанить", CallbackResponse::User(user_id).to_msg_text().unwrap())
+    ]]);
+    if info.until.is_some() {
+        let mut row: Vec<_> = BAN_EXTENSIONS.iter().enumerate()
+            .map(|(i, (text, _))|InlineKeyboardButton::callback(*text, CallbackResponse::ExtendBan(user_id, Some(i)).to_msg_text().unwrap()))
+            .collect();
+        row.push(InlineKeyboardButton::callback("Навсегда", CallbackResponse::ExtendBan(user_id, None).to_msg_text().unwrap()));
+        markup = markup.append_row(row);
+    }
+    markup = markup.append_row(vec![
+        InlineKeyboardButton::callback("К списку", CallbackResponse::BannedPage(page).to_msg_text().unwrap())
+    ]);
+    (text, markup)
+}
+
+async fn on_banned_search(
+    bot: WBot,
+    dialogue: MyDialogue,
+    content: Content,
+    conf: Conf,
+) -> FSMResult {
+    if let Content::Text(text) = content {
+        let query = text.text.trim().to_owned();
+        let (text, markup) = markup_banned(&conf, &query, 0);
+        bot.send_message(dialogue.chat_id(), text).reply_markup(markup).await?;
+        dialogue.update(State::WaitSelectBanned(query)).await?;
     } else {
-        bail!("Expects callback query, but not")
+        bot.send_message(dialogue.chat_id(), "Для поиска пришли текст").await?;
     }
     Ok(())
 }
 
+const JOIN_REQUEST_FAILED: &str = "Не удалось: заявка устарела или человек уже вступил";
+
+/// Сообщает админу, если заявку уже рассмотрел кто-то другой
+async fn join_request_decided(bot: &WBot, conf: &Conf, upd: &Update, user_id: UserId, chat_id: ChatId) -> Result<bool> {
+    match conf.join_request(user_id, chat_id) {
+        Some(JoinRequest { decision: Some(previous), .. }) => {
+            edit_callback_message(bot.clone(), upd.clone(), &format!("Заявку уже рассмотрели: {previous}")).await?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Записывает решение по уже выполненной заявке и показывает его на карточках всех админов
+async fn decide_join_request(bot: &WBot, conf: &Conf, upd: Update, user_id: UserId, chat_id: ChatId, decision: &str) -> FSMResult {
+    let admin = upd.user().map(make_username).unwrap_or_default();
+    let decision = format!("{decision} ({admin})");
+    match conf.decide_join_request(user_id, chat_id, decision.clone()) {
+        Some(request) => {
+            let text = format!("{}\n\n{decision}", request.text);
+            for (chat, card) in request.cards {
+                bot.edit_message_text(chat, card, text.as_str()).await.ok_or_log();
+            }
+        }
+        // заявка пришла до перезапуска бота и не отслеживается
+        None => edit_callback_message(bot.clone(), upd, &decision).await?,
+    }
+    Ok(())
+}
+
+/// Закрывает жалобу и показывает решение на карточках всех админов.
+/// Возвращает false, если жалобу уже рассмотрел кто-то другой
+async fn resolve_report(bot: &WBot, conf: &Conf, upd: Update, (channel, post): (ChatId, i32), decision: &str) -> Result<bool> {
+    let admin = upd.user().map(make_username).unwrap_or_default();
+    let decision = format!("{decision} ({admin})");
+    match conf.close_report(channel, post) {
+        Some(report) => {
+            let text = format!("{}\n\n{decision}", report.text);
+            for (chat, card) in report.cards {
+                bot.edit_message_text(chat, card, text.as_str()).await.ok_or_log();
+            }
+            Ok(true)
+        }
+        None => {
+            edit_callback_message(bot.clone(), upd, "Жалобу уже рассмотрели").await?;
+            Ok(false)
+        }
+    }
+}
+
 async fn on_wait_forward(
     bot: WBot,
     dialogue: MyDialogue,
-    content: Content
+    content: Content,
+    conf: Conf,
 ) -> FSMResult {
-    if let Some(user_id) = invoke_author(&content) {
-        dialogue.update(State::WaitCause(user_id)).await?;
-        bot.send_message(dialogue.chat_id(), "Пиши причину").await?;
+    let user_id = match invoke_author(&content) {
+        Some(user_id) => Some(user_id),
+        None => resolve_user(&bot, &conf, &content).await,
+    };
+    if let Some(user_id) = user_id {
+        dialogue.update(State::WaitCause(user_id, None)).await?;
+        bot.send_message(dialogue.chat_id(), "Пиши причину. Чтобы забанить на время (от 30 секунд до 366 дней), начни со срока, например: 7d спам").await?;
     } else {
-        bot.send_message(dialogue.chat_id(), "Это не публикация").await?;
+        bot.send_message(dialogue.chat_id(), format!("Это не публикация и не ссылка на пользователя. {USERNAME_HINT}")).await?;
     }
     Ok(())
 }
 
 async fn on_wait_cause(
+    bot: WBot,
+    dialogue: MyDialogue,
+    upd: Update,
+    content: Content,
+    (user_id, report): (UserId, Option<(ChatId, i32)>),
+    conf: Conf,
+) -> FSMResult {
+    if let Content::Text(text) = content {
+        let Ok((duration, cause)) = parse_ban_cause(&text.text) else {
+            bot.send_message(dialogue.chat_id(), conf.template(Template::BanTermOutOfRange)).await?;
+            return Ok(())
+        };
+        if report.is_some_and(|(channel, post)|!conf.is_report_open(channel, post)) {
+            bot.send_message(dialogue.chat_id(), "Жалобу уже рассмотрели").await?;
+            dialogue.exit().await?;
+            return Ok(())
+        }
+        let name = user_name(&bot, &conf, user_id).await;
+        let until = duration.map(|duration|(chrono::Utc::now() + duration).timestamp());
+        let cause = if cause.is_empty() { NO_REASON } else { cause }.to_owned();
+        let info = BanInfo {name, cause, until};
+        let term = ban_term(&info);
+        log_moderation(&bot, &conf, LogEntry::new(dialogue.user_id(), "Бан").target(user_id).reason(format!("{} ({term})", info.cause))).await;
+        ban_user(&bot, &conf, user_id, info).await;
+        if let Some(report) = report {
+            resolve_report(&bot, &conf, upd, report, "⛔ Автор забанен").await?;
+        }
+        bot.send_message(dialogue.chat_id(), format!("Забанен {term}")).await?;
+        dialogue.exit().await?;
+    } else {
+        bot.send_message(dialogue.chat_id(), "Причину укажи просто текстом").await?;
+    }
+    Ok(())
+}
+
+async fn on_wait_forward_for_warn(
+    bot: WBot,
+    dialogue: MyDialogue,
+    content: Content,
+    conf: Conf,
+) -> FSMResult {
+    let user_id = match invoke_author(&content) {
+        Some(user_id) => Some(user_id),
+        None => resolve_user(&bot, &conf, &content).await,
+    };
+    if let Some(user_id) = user_id {
+        dialogue.update(State::WaitWarnCause(user_id)).await?;
+        bot.send_message(dialogue.chat_id(), "Пиши причину предупреждения").await?;
+    } else {
+        bot.send_message(dialogue.chat_id(), format!("Это не публикация и не ссылка на пользователя. {USERNAME_HINT}")).await?;
+    }
+    Ok(())
+}
+
+async fn on_wait_warn_cause(
     bot: WBot,
     dialogue: MyDialogue,
     content: Content,
@@ -123,12 +448,9 @@ async fn on_wait_cause(
     conf: Conf,
 ) -> FSMResult {
     if let Content::Text(text) = content {
-        let name = bot.get_chat_member(conf.channel, user_id).await
-            .ok().map( |u|format!("{} {}", u.user.first_name, u.user.last_name.unwrap_or_default() ))
-            .unwrap_or(format!("[{}]", user_id));
-        let info = BanInfo {name, cause: text.text};
-        conf.ban(user_id, info);
-        bot.send_message(dialogue.chat_id(), "Забанен").await?;
+        let report = warn_user(&bot, &conf, user_id, text.text).await?;
+        log_moderation(&bot, &conf, LogEntry::new(dialogue.user_id(), "Предупреждение").target(user_id).reason(report.as_str())).await;
+        bot.send_message(dialogue.chat_id(), report).await?;
         dialogue.exit().await?;
     } else {
         bot.send_message(dialogue.chat_id(), "Причину укажи просто текстом").await?;
@@ -136,14 +458,113 @@ async fn on_wait_cause(
     Ok(())
 }
 
-async fn on_wait_forward_for_admin(upd: Update, dialogue: MyDialogue, conf: Conf, bot: WBot) -> FSMResult {
-    if let teloxide::types::UpdateKind::Message(msg) = upd.kind {
-        if let Some(admin) = msg.forward_from_user() {
-            conf.add_admin(admin.id, make_username(admin));
-            bot.send_message(dialogue.chat_id(), "Отлично! Новый админ добавлен!").await?;
-            return Ok(())
+async fn on_wait_forward_for_takedown(
+    bot: WBot,
+    dialogue: MyDialogue,
+    content: Content,
+    upd: Update,
+    conf: Conf,
+) -> FSMResult {
+    let forwarded = match &upd.kind {
+        UpdateKind::Message(msg) => msg.forward_from_chat().map(|chat|chat.id)
+            .filter(|chat|conf.channels().contains(chat))
+            .zip(msg.forward_from_message_id()),
+        _ => None,
+    };
+    let target = match (forwarded.and_then(|(channel, id)|conf.find_channel_ad(channel, id)), forwarded) {
+        (Some(ad), _) => Some((ad.user_id, ad.channel, ad.messages)),
+        (None, Some((channel, id))) => invoke_author(&content).map(|author|(author, channel, vec![id])),
+        _ => None,
+    };
+    if let Some((author, channel, messages)) = target {
+        request_takedown_reason(bot, dialogue, author, channel, messages).await?;
+    } else {
+        bot.send_message(dialogue.chat_id(), "Это не публикация из канала").await?;
+    }
+    Ok(())
+}
+
+async fn request_takedown_reason(bot: WBot, dialogue: MyDialogue, author: UserId, channel: ChatId, messages: Vec<i32>) -> FSMResult {
+    let markup = InlineKeyboardMarkup::new(TAKEDOWN_REASONS.iter().enumerate().map(|(i, reason)|{
+        vec![InlineKeyboardButton::callback(*reason, CallbackResponse::TakedownReason(i).to_msg_text().unwrap())]
+    }));
+    bot.send_message(dialogue.chat_id(), "Выбери причину или напиши свою").reply_markup(markup).await?;
+    dialogue.update(State::WaitTakedownReason(author, channel, messages)).await?;
+    Ok(())
+}
+
+async fn on_wait_takedown_reason(
+    bot: WBot,
+    dialogue: MyDialogue,
+    content: Content,
+    (author, channel, messages): (UserId, ChatId, Vec<i32>),
+    conf: Conf,
+) -> FSMResult {
+    if let Content::Text(text) = content {
+        take_down(bot, conf, dialogue, author, channel, messages, text.text).await?;
+    } else {
+        bot.send_message(dialogue.chat_id(), "Причину укажи просто текстом").await?;
+    }
+    Ok(())
+}
+
+/// Удаляет публикацию из канала и сообщает автору причину
+async fn take_down(bot: WBot, conf: Conf, dialogue: MyDialogue, author: UserId, channel: ChatId, messages: Vec<i32>, reason: String) -> FSMResult {
+    let post = messages.first().copied().unwrap_or_default();
+    let ad = conf.find_channel_ad(channel, post);
+    dialogue.exit().await?;
+    if let Err(e) = delete_msgs(&bot, channel, messages).await {
+        log::error!("Err on take down ad: {:?}", e);
+        bot.send_message(dialogue.chat_id(), "Не удалось удалить объявление. Возможно, прошло более 48 часов").await?;
+        return Ok(())
+    }
+    let mut text = format!("{} {reason}", conf.template(Template::AdTakenDown));
+    if let Some(ad) = ad {
+        conf.remove_ad(ad.channel, ad.post());
+        on_ad_removed(&bot, &conf, &ad);
+        text = format!("{text}\n\n{}", ad.text);
+    }
+    log_moderation(&bot, &conf, LogEntry::new(dialogue.user_id(), "Объявление снято").target(author).reason(reason)).await;
+    let markup = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Обжаловать", CallbackResponse::Appeal(channel, post).to_msg_text()?)
+    ]]);
+    let notified = bot.send_message(ChatId::from(author), text).reply_markup(markup).await.ok_or_log().is_some();
+    let report = if notified { "Объявление снято, автор уведомлен" } else { "Объявление снято, но автора уведомить не удалось" };
+    bot.send_message(dialogue.chat_id(), report).await?;
+    Ok(())
+}
+
+async fn on_wait_forward_for_admin(upd: Update, dialogue: MyDialogue, conf: Conf, bot: WBot, content: Content) -> FSMResult {
+    let forwarded = match upd.kind {
+        teloxide::types::UpdateKind::Message(msg) => msg.forward_from_user().map(|user|(user.id, make_username(user))),
+        _ => None,
+    };
+    let admin = match forwarded {
+        Some(admin) => Some(admin),
+        None => match resolve_user(&bot, &conf, &content).await {
+            Some(user_id) => {
+                let name = bot.get_chat_member(conf.channel, user_id).await
+                    .map(|member|make_username(&member.user))
+                    .unwrap_or_else(|_|format!("[{}]", user_id));
+                Some((user_id, name))
+            }
+            None => None,
         }
+    };
+    let Some((admin_id, name)) = admin else {
+        bot.send_message(dialogue.chat_id(), format!("Это не то. Нужно переслать любое сообщение от человека, которого ты хочешь сделать админом, или прислать его id или ссылку tg://user?id=. {USERNAME_HINT}")).await?;
+        return Ok(())
+    };
+    if conf.role(&admin_id) == Some(Role::Owner) {
+        bot.send_message(dialogue.chat_id(), "Это владелец бота, его роль менять нельзя").await?;
+        return Ok(())
     }
-    bot.send_message(dialogue.chat_id(), "Это не то. Нужно переслать любое сообщение от человека, которого ты хочешь сделать админом").await?;
+    let markup = InlineKeyboardMarkup::new(vec![[Role::Admin, Role::Moderator].into_iter()
+        .map(|role|InlineKeyboardButton::callback(role.name(), CallbackResponse::AdminRole(role).to_msg_text().unwrap()))
+        .collect::<Vec<_>>()
+    ]);
+    dialogue.update(State::WaitAdminRole(admin_id, name)).await?;
+    bot.send_message(dialogue.chat_id(), "Какую роль выдать? Админ может банить, принимать заявки и удалять объявления, модератор - только банить и удалять объявления")
+        .reply_markup(markup).await?;
     Ok(())
 }
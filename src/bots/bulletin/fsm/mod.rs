@@ -1,17 +1,35 @@
-use teloxide::dispatching::dialogue::Storage;
-use teloxide::payloads::{SendMessageSetters, RestrictChatMemberSetters};
-use teloxide::types::{ParseMode, InlineKeyboardMarkup, InlineKeyboardButton, UserId, ChatPermissions, UpdateKind, ChatJoinRequest};
+use teloxide::payloads::{SendMessageSetters, RestrictChatMemberSetters, EditMessageTextSetters, BanChatMemberSetters, UnbanChatMemberSetters, AnswerInlineQuerySetters};
+use teloxide::types::{ParseMode, InlineKeyboardMarkup, InlineKeyboardButton, UserId, ChatPermissions, UpdateKind, ChatJoinRequest, MessageId, InlineQuery, InlineQueryResult, InlineQueryResultArticle, InputMessageContent, InputMessageContentText};
 
 use self::admin::process_admin;
 use self::user::process_user;
 
-use super::config::Template;
-use super::impls::send_ad;
+use super::config::{Template, CaptchaPurpose, PendingCaptcha};
+use super::captcha::Captcha;
+use crate::persistent::PublishedAd;
+use std::collections::HashSet;
+use super::impls::{self, send_ad};
 use super::*;
 
 
 type MyDialogue = Dialogue<State, MyStorage>;
 
+const SERVICE_MESSAGE_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+const DEFAULT_MUTE: chrono::Duration = chrono::Duration::weeks(2);
+const SPAM_MUTE: chrono::Duration = chrono::Duration::days(1);
+const MAX_MENTIONS: usize = 5;
+const CAPTCHA_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+//начиная с этого повтора одинаковый комментарий считается спамом
+const REPEAT_LIMIT: usize = 3;
+const INLINE_RESULTS_LIMIT: usize = 50;
+const INLINE_TITLE_LEN: usize = 64;
+const INLINE_CACHE_TIME: u32 = 30;
+//комментарии, пришедшие за это время, собираются в одно уведомление
+const COMMENT_BATCH_DELAY: std::time::Duration = std::time::Duration::from_secs(60);
+const NO_REASON: &str = "не указана";
+//пользователей по @username бот знает только с момента последнего запуска
+const USERNAME_HINT: &str = "По @username находятся только админы канала и те, кто писал боту или в обсуждение после его последнего перезапуска";
+
 mod user;
 mod admin;
 
@@ -26,10 +44,25 @@ pub enum State {
     Filling(Ad),
     Preview(Ad),
     WaitForward,
-    WaitCause(UserId),
-    WaitSelectBanned,
+    /// Ждем причину бана. Если бан по жалобе - канал и пост жалобы
+    WaitCause(UserId, Option<(ChatId, i32)>),
+    WaitSelectBanned(String),
     WaitForwardForAdmin,
-    Subscribing(ChatId),
+    WaitAdminRole(UserId, String),
+    WaitForwardForWarn,
+    WaitWarnCause(UserId),
+    WaitForwardForTakedown,
+    /// Автор, канал и сообщения снимаемого объявления
+    WaitTakedownReason(UserId, ChatId, Vec<i32>),
+    /// Канал и пост снятого объявления, полученное автором уведомление с причиной
+    Appealing(ChatId, i32, String),
+    /// Заявка на вступление в чат: ответы на уже заданные вопросы анкеты
+    Subscribing(ChatId, Vec<String>),
+    WaitSavedSearch,
+    /// Объявление закрыто как проданное, ждем покупателя: канал, пост и заголовок объявления
+    WaitBuyer(ChatId, i32, String),
+    /// Оценка сделки поставлена, ждем текст отзыва
+    WaitReview(ChatId, i32, u8),
 }
 
 impl Default for State {
@@ -43,15 +76,72 @@ pub fn make_dialogue_handler() -> FSMHandler {
     .enter_dialogue::<Signal, MyStorage, State>()
     .branch(process_user(dptree::entry()))
     .branch(process_admin(dptree::filter(filter_admin)))
+    .branch(dptree::filter_map(Signal::filter_admin_action).endpoint(on_forbidden))
     .endpoint(on_wrong_message);
-    let group_handler = dptree::filter_map(GroupMessage::from_update)
-        .endpoint(on_group_message_with_delete_aliens);
+    let group_handler = dptree::filter_map(filter_group_message)
+        .endpoint(on_group_message_with_delete_spam);
     dptree::entry()
+    .inspect(remember_user)
+    .branch(Update::filter_inline_query().endpoint(on_inline_query))
     .branch(dptree::filter_map(filter_join_request).endpoint(on_join_request))
+    .branch(dptree::filter_map(filter_channel_callback).endpoint(on_channel_callback))
+    .branch(Update::filter_edited_channel_post().endpoint(on_edited_post))
     .branch(dptree::filter(filter_private).chain(private_handler))
     .branch(group_handler)
 }
 
+fn remember_user(upd: Update, conf: Conf) {
+    if let Some(user) = upd.user() {
+        conf.remember_user(user);
+    }
+}
+
+/// Поиск по объявлениям в inline-режиме: `@bot iphone до 50000`
+async fn on_inline_query(bot: WBot, conf: Conf, q: InlineQuery) -> FSMResult {
+    let query = AdQuery::parse(&q.query);
+    let results: Vec<_> = conf.search_ads(&query, INLINE_RESULTS_LIMIT).into_iter().filter_map(|ad|{
+        let url = conf.post_url(&ad)?;
+        let title: String = ad.text.lines().find(|l|!l.trim().is_empty())?.chars().take(INLINE_TITLE_LEN).collect();
+        let summary = impls::ad_summary(&ad, &conf);
+        let content = InputMessageContentText::new(format!("{}\n{}\n{}", title, summary, url));
+        let article = InlineQueryResultArticle::new(format!("{}_{}", ad.channel, ad.post()), title, InputMessageContent::Text(content))
+            .description(summary);
+        Some(InlineQueryResult::Article(article))
+    }).collect();
+    bot.answer_inline_query(q.id, results).cache_time(INLINE_CACHE_TIME).await?;
+    Ok(())
+}
+
+fn filter_channel_callback(upd: Update) -> Option<CallbackQuery> {
+    match upd.kind {
+        UpdateKind::CallbackQuery(q) if q.message.as_ref().is_some_and(|msg|msg.chat.is_channel()) => Some(q),
+        _ => None,
+    }
+}
+
+/// Кнопка «В избранное» под постом: открывает бота со ссылкой на добавление поста
+async fn on_channel_callback(bot: WBot, conf: Conf, q: CallbackQuery) -> FSMResult {
+    let data = q.data.as_deref().unwrap_or_default();
+    if let (Ok(CallbackResponse::Favorite), Some(msg)) = (CallbackResponse::from_mst_text(data), q.message.as_ref()) {
+        if let Some(url) = conf.favorite_url(msg.chat.id, msg.id.0).and_then(|url|url.parse().ok()) {
+            bot.answer_callback_query(q.id).url(url).await?;
+            return Ok(());
+        }
+    }
+    bot.answer_callback_query(q.id).await?;
+    Ok(())
+}
+
+/// Админ канала отредактировал пост: сообщаем тем, у кого объявление в избранном
+async fn on_edited_post(bot: WBot, conf: Conf, msg: Message) -> FSMResult {
+    let Some(ad) = conf.find_channel_ad(msg.chat.id, msg.id.0) else {
+        return Ok(())
+    };
+    let users = conf.favorited_by(ad.channel, ad.post());
+    send_favorites_notice(&bot, &conf, &ad, users, Template::FavoriteEdited);
+    Ok(())
+}
+
 fn filter_join_request(upd: Update) -> Option<ChatJoinRequest> {
     if let UpdateKind::ChatJoinRequest(jr) = upd.kind {
         Some(jr)
@@ -67,17 +157,119 @@ async fn on_join_request(
     jr: ChatJoinRequest,
 ) -> FSMResult {
     let chat_id = ChatId(jr.from.id.0 as i64);
+    if let Some((false, rule)) = auto_decision(&conf, &jr.from) {
+        bot.decline_chat_join_request(jr.chat.id, jr.from.id).await?;
+        log_auto_decision(&bot, &conf, jr.from.id, "Заявка на вступление отклонена автоматически", rule).await;
+        return Ok(())
+    }
     if let Some(persistent::BanInfo{cause,..}) = conf.is_banned(&jr.from.id) {
         bot.send_message(chat_id, format!("Ты в бане. Причина: {cause}")).await?;
         return Ok(())
     }
-    storage.update_dialogue(chat_id, State::Subscribing(jr.chat.id)).await?;
-    bot.send_message(chat_id, conf.template(Template::SubscribeInfo)).await?;
+    // автоправила принятия применяются только после капчи
+    if conf.needs_captcha(&jr.from.id) {
+        return send_captcha(&bot, &conf, jr.from.id, CaptchaPurpose::Join(jr.chat.id)).await
+    }
+    admit_join_request(&bot, &conf, &MyDialogue::new(storage, chat_id), &jr.from, jr.chat.id).await
+}
+
+/// Принимает заявку по автоправилу, иначе начинает анкету для админов
+async fn admit_join_request(bot: &WBot, conf: &Conf, dialogue: &MyDialogue, user: &teloxide::types::User, chat_id: ChatId) -> FSMResult {
+    if let Some((true, rule)) = auto_decision(conf, user) {
+        bot.approve_chat_join_request(chat_id, user.id).await?;
+        bot.send_message(dialogue.chat_id(), conf.template(Template::JoinApproved)).await.ok_or_log();
+        log_auto_decision(bot, conf, user.id, "Заявка на вступление принята автоматически", rule).await;
+        return Ok(())
+    }
+    dialogue.update(State::Subscribing(chat_id, vec![])).await?;
+    send_join_intro(bot, conf, dialogue.chat_id()).await
+}
+
+async fn log_auto_decision(bot: &WBot, conf: &Conf, user_id: UserId, action: &'static str, rule: String) {
+    log::info!("{action}: user {user_id}, rule: {rule}");
+    if let Some(me) = conf.bot_id() {
+        log_moderation(bot, conf, LogEntry::new(me, action).target(user_id).reason(rule)).await;
+    }
+}
+
+/// Начало заявки на вступление: первый вопрос анкеты или просьба написать админам
+async fn send_join_intro(bot: &WBot, conf: &Conf, chat_id: ChatId) -> FSMResult {
+    match conf.questions.first() {
+        Some(question) => {
+            bot.send_message(chat_id, conf.template(Template::QuestionnaireInfo)).await?;
+            send_question(bot, chat_id, question).await?;
+        }
+        None => {
+            bot.send_message(chat_id, conf.template(Template::SubscribeInfo)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Решение автоправил по заявке на вступление: принять (true) или отклонить (false) и по какому правилу.
+/// None - заявку рассматривают админы
+fn auto_decision(conf: &Conf, user: &teloxide::types::User) -> Option<(bool, String)> {
+    if let Some(info) = conf.is_banned(&user.id) {
+        return conf.auto_decline_banned().then(||(false, format!("в бане: {}", info.cause)))
+    }
+    if conf.auto_approve_allowlist() && conf.in_allowlist(&user.id) {
+        Some((true, "в белом списке".to_owned()))
+    } else if conf.auto_approve_authors() && conf.is_author(&user.id) {
+        Some((true, "уже публиковал объявления".to_owned()))
+    } else if conf.auto_approve_username() && user.username.is_some() {
+        Some((true, "есть username".to_owned()))
+    } else {
+        None
+    }
+}
+
+fn filter_admin(upd: Update, signal: Signal, conf: Conf) -> bool {
+    let Some(user) = upd.user() else {
+        return false
+    };
+    match signal.filter_admin_action() {
+        Some(action) => conf.can(&user.id, action.permission()),
+        None => conf.is_admin(&user.id),
+    }
+}
+
+async fn on_forbidden(bot: WBot, dialogue: MyDialogue, conf: Conf) -> FSMResult {
+    bot.send_message(dialogue.chat_id(), conf.template(Template::AdminsOnly)).await?;
     Ok(())
 }
 
-fn filter_admin(upd: Update, conf: Conf) -> bool {
-    upd.user().map(|user|conf.is_admin(&user.id)).unwrap_or(false)
+fn filter_group_message(upd: Update) -> Option<Message> {
+    match upd.kind {
+        UpdateKind::Message(msg) if msg.chat.is_group() || msg.chat.is_supergroup() => Some(msg),
+        _ => None,
+    }
+}
+
+/// Спам проверяется до разбора комментария: подписи к фото и пересылки тоже бывают спамом
+async fn on_group_message_with_delete_spam(msg: Message, bot: WBot, conf: Conf) -> FSMResult {
+    let author = msg.from().map(|user|user.id);
+    let reason = author.and_then(|author|spam_reason(author, &SpamSigns::from_message(&msg), &conf));
+    if let (Some(author), Some(reason)) = (author, reason) {
+        bot.delete_message(msg.chat.id, msg.id).await?;
+        log::info!("Комментарий {} удален как спам: {reason}", msg.url().map(|url|url.to_string()).unwrap_or_default());
+        let (action, until) = if conf.spam_mute() {
+            ("Спам удален, автор в мьюте", Some(mute(&bot, msg.chat.id, author, SPAM_MUTE).await?))
+        } else {
+            ("Спам удален", None)
+        };
+        if let Some(me) = conf.bot_id() {
+            let mut entry = LogEntry::new(me, action).target(author).reason(reason);
+            if let Some(until) = until {
+                entry = entry.until(until);
+            }
+            log_moderation(&bot, &conf, entry).await;
+        }
+        return Ok(())
+    }
+    match GroupMessage::from_message(msg, conf.clone()) {
+        Some(msg) => on_group_message_with_delete_aliens(msg, bot, conf).await,
+        None => Ok(()),
+    }
 }
 
 async fn on_group_message_with_delete_aliens(msg: GroupMessage, bot: WBot, conf: Conf) -> FSMResult {
@@ -85,9 +277,10 @@ async fn on_group_message_with_delete_aliens(msg: GroupMessage, bot: WBot, conf:
     let is_alien = if author.is_telegram() || author.is_anonymous() {
         false
     } else if author.is_channel() {
-        msg.sender_chat_id != Some(conf.channel)
+        msg.sender_chat_id.is_none_or(|chat|!conf.channels().contains(&chat))
     } else {
-        let chat_member = bot.get_chat_member(conf.channel, author).await?;
+        let channel = linked_channel(&bot, &conf, msg.chat_id).await;
+        let chat_member = bot.get_chat_member(channel, author).await?;
         chat_member.is_left() || chat_member.is_banned()
     };
     if is_alien && conf.only_subscribers() {
@@ -99,36 +292,498 @@ async fn on_group_message_with_delete_aliens(msg: GroupMessage, bot: WBot, conf:
     }
 }
 
+/// Проверяет сообщение в обсуждении по включенным для бота антиспам-правилам
+fn spam_reason(author: UserId, signs: &SpamSigns, conf: &Conf) -> Option<&'static str> {
+    if author.is_telegram() || author.is_anonymous() || author.is_channel() || conf.is_admin(&author) {
+        return None
+    }
+    if conf.spam_invites() && signs.invite_links {
+        Some("ссылка-приглашение")
+    } else if conf.spam_links() && signs.links {
+        Some("ссылка")
+    } else if conf.spam_forwards() && signs.forwarded_post {
+        Some("пересланный пост канала")
+    } else if conf.spam_mentions() && signs.mentions > MAX_MENTIONS {
+        Some("слишком много упоминаний")
+    } else if conf.spam_repeats() && signs.text.as_ref().map(|text|conf.count_repeats(author, text) >= REPEAT_LIMIT).unwrap_or(false) {
+        Some("повтор одного и того же сообщения")
+    } else {
+        None
+    }
+}
+
+async fn mute(bot: &WBot, chat_id: ChatId, user_id: UserId, duration: chrono::Duration) -> Result<chrono::DateTime<chrono::Utc>> {
+    let duration = duration.clamp(MIN_RESTRICTION, MAX_RESTRICTION);
+    let until = chrono::Utc::now().checked_add_signed(duration).ok_or(anyhow!("mute term overflow"))?;
+    bot.restrict_chat_member(chat_id, user_id, ChatPermissions::empty()).until_date(until).await?;
+    Ok(until)
+}
+
 async fn on_group_message(msg: GroupMessage, bot: WBot, conf: Conf) -> FSMResult {
-    match msg.kind {
+    match msg.kind.clone() {
         GroupMessageKind::Comment { thread, replied_author } => if replied_author != msg.author { 
-            let chat_id = teloxide::types::ChatId(replied_author.0 as i64);
-            let text = conf.template(Template::NewComment);
-            let text = impls::make_message_link(text, &msg.url, Some(thread)).unwrap_or(text.into());
-            bot.send_message(chat_id, text).parse_mode(ParseMode::MarkdownV2).await?;
+            let channel = linked_channel(&bot, &conf, msg.chat_id).await;
+            notify_comment(&bot, &conf, replied_author, (channel, thread), &msg.url, Template::NewComment);
+        },
+        GroupMessageKind::Reply { thread, replied_author } => if replied_author != msg.author {
+            let channel = linked_channel(&bot, &conf, msg.chat_id).await;
+            notify_comment(&bot, &conf, replied_author, (channel, thread), &msg.url, Template::NewReply);
+        },
+        GroupMessageKind::Mute(user_id, duration) => {
+            if conf.can(&msg.author, roles::BAN) {
+                let until = mute(&bot, msg.chat_id, user_id, duration.unwrap_or(DEFAULT_MUTE)).await?;
+                let text = format!("{} {}", conf.template(Template::Muted), until.format("%d.%m.%Y %H:%M (UTC)"));
+                log_moderation(&bot, &conf, LogEntry::new(msg.author, "Мьют")
+                    .target(user_id).until(until).link(msg.url.as_str())).await;
+                send_service_message(bot, &msg, text).await?;
+            } else {
+                bot.send_message(msg.chat_id, conf.template(Template::AdminsOnly)).reply_to_message_id(msg.id).await?;
+            }
         },
-        GroupMessageKind::Mute(user_id) => {
-            if conf.is_admin(&msg.author) {
-                let until = chrono::Utc::now() + chrono::Duration::weeks(2);
-                bot.restrict_chat_member(msg.chat_id, user_id, ChatPermissions::empty()).until_date(until).await?;
+        GroupMessageKind::Unmute(user_id) => {
+            if conf.can(&msg.author, roles::BAN) {
+                let permissions = bot.get_chat(msg.chat_id).await?.permissions().unwrap_or(ChatPermissions::all());
+                bot.restrict_chat_member(msg.chat_id, user_id, permissions).await?;
+                log_moderation(&bot, &conf, LogEntry::new(msg.author, "Снят мьют").target(user_id).link(msg.url.as_str())).await;
+                send_service_message(bot, &msg, conf.template(Template::Unmuted).into()).await?;
             } else {
                 bot.send_message(msg.chat_id, conf.template(Template::AdminsOnly)).reply_to_message_id(msg.id).await?;
             }
         },
-        GroupMessageKind::Ban(user_id) => {
-            if conf.is_admin(&msg.author) {
+        GroupMessageKind::Ban(user_id, cause) => {
+            if conf.can(&msg.author, roles::BAN) {
+                let Ok((duration, cause)) = parse_ban_cause(&cause) else {
+                    bot.send_message(msg.chat_id, conf.template(Template::BanTermOutOfRange)).reply_to_message_id(msg.id).await?;
+                    return Ok(())
+                };
                 log::info!("user with id {user_id} goes to ban");
-                bot.ban_chat_member(conf.channel, user_id).await.ok_or_log();
-                bot.ban_chat_member(msg.chat_id, user_id).await.ok_or_log();
+                let until = duration.map(|duration|(chrono::Utc::now() + duration).timestamp());
+                let cause = if cause.is_empty() { NO_REASON } else { cause }.to_owned();
+                let name = user_name(&bot, &conf, user_id).await;
+                let info = persistent::BanInfo { name, cause, until };
+                let reason = format!("{} ({})", info.cause, admin::ban_term(&info));
+                ban_user(&bot, &conf, user_id, info).await;
+                log_moderation(&bot, &conf, LogEntry::new(msg.author, "Бан в канале и группе")
+                    .target(user_id).reason(reason).link(msg.url.as_str())).await;
             } else {
                 bot.send_message(msg.chat_id, conf.template(Template::AdminsOnly)).reply_to_message_id(msg.id).await?;
             }
         },
+        GroupMessageKind::Warn(user_id, cause) => {
+            if conf.can(&msg.author, roles::BAN) {
+                let cause = if cause.is_empty() { NO_REASON.to_owned() } else { cause };
+                let text = warn_user(&bot, &conf, user_id, cause).await?;
+                log_moderation(&bot, &conf, LogEntry::new(msg.author, "Предупреждение")
+                    .target(user_id).reason(text.as_str()).link(msg.url.as_str())).await;
+                send_service_message(bot, &msg, text).await?;
+            } else {
+                bot.send_message(msg.chat_id, conf.template(Template::AdminsOnly)).reply_to_message_id(msg.id).await?;
+            }
+        },
+        GroupMessageKind::Report { author, post, reason } => {
+            let reporter = user_name(&bot, &conf, msg.author).await;
+            let reason = if reason.is_empty() { NO_REASON.to_owned() } else { reason };
+            let text = format!("Жалоба на объявление от {reporter}\nПричина: {reason}");
+            let channel = linked_channel(&bot, &conf, msg.chat_id).await;
+            if !conf.open_report(msg.author, channel, post, text.clone()) {
+                send_service_message(bot, &msg, conf.template(Template::ReportAlreadySent).into()).await?;
+                return Ok(())
+            }
+            let callback = |text, data: CallbackResponse| vec![InlineKeyboardButton::callback(text, data.to_msg_text().unwrap())];
+            let markup = InlineKeyboardMarkup::new(vec![
+                callback("Удалить объявление", CallbackResponse::ReportDelete(channel, post)),
+                callback("Забанить автора", CallbackResponse::ReportBan(author, channel, post)),
+                callback("Отклонить", CallbackResponse::ReportDismiss(channel, post)),
+            ]);
+            for admin_id in conf.admins_with(roles::REMOVE_ADS) {
+                bot.forward_message(admin_id, channel, MessageId(post)).await.ok_or_log();
+                if let Some(card) = bot.send_message(admin_id, text.as_str()).reply_markup(markup.clone()).await.ok_or_log() {
+                    conf.add_report_card(channel, post, (card.chat.id, card.id));
+                }
+            }
+            send_service_message(bot, &msg, conf.template(Template::ReportSent).into()).await?;
+        },
+        GroupMessageKind::MuteUsage => if conf.can(&msg.author, roles::BAN) {
+            let text = format!("{} {}", conf.template(Template::MuteCommand), conf.template(Template::MuteUsage));
+            bot.send_message(msg.chat_id, text).reply_to_message_id(msg.id).await?;
+        },
         GroupMessageKind::Dumb => {},
     }
     Ok(())
 }
 
+/// Выносит предупреждение и, если их набралось достаточно, мьютит или банит пользователя.
+/// Возвращает текст для админа
+async fn warn_user(bot: &WBot, conf: &Conf, user_id: UserId, cause: String) -> Result<String> {
+    let warnings = conf.warn(user_id, cause.clone());
+    let count = warnings.len();
+    let limit = conf.warn_limit();
+    let mut text = format!("{} ({count}/{limit}): {cause}", conf.template(Template::Warned));
+    bot.send_message(ChatId::from(user_id), text.as_str()).await.ok_or_log();
+    if count < limit {
+        return Ok(text)
+    }
+    if conf.warn_ban() {
+        let name = user_name(bot, conf, user_id).await;
+        let cause = warnings.into_iter().map(|w|w.cause).collect::<Vec<_>>().join("; ");
+        ban_user(bot, conf, user_id, persistent::BanInfo { name, cause, until: None }).await;
+        text.push_str("\nЛимит предупреждений исчерпан, пользователь забанен");
+    } else {
+        let groups = discussion_groups(bot, conf).await;
+        if groups.is_empty() {
+            text.push_str("\nЛимит предупреждений исчерпан, но у канала нет группы обсуждения, замьютить негде");
+            return Ok(text)
+        }
+        for group in groups {
+            mute(bot, group, user_id, DEFAULT_MUTE).await.ok_or_log();
+        }
+        text.push_str("\nЛимит предупреждений исчерпан, пользователь не сможет писать две недели");
+    }
+    Ok(text)
+}
+
+/// Запись для лога модерации
+struct LogEntry {
+    actor: UserId,
+    action: &'static str,
+    target: Option<UserId>,
+    reason: Option<String>,
+    until: Option<chrono::DateTime<chrono::Utc>>,
+    link: Option<String>,
+}
+
+impl LogEntry {
+    fn new(actor: UserId, action: &'static str) -> Self {
+        Self { actor, action, target: None, reason: None, until: None, link: None }
+    }
+    fn target(mut self, target: UserId) -> Self {
+        self.target = Some(target);
+        self
+    }
+    fn reason<S: Into<String>>(mut self, reason: S) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+    fn until(mut self, until: chrono::DateTime<chrono::Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+    fn link<S: Into<String>>(mut self, link: S) -> Self {
+        self.link = Some(link.into());
+        self
+    }
+}
+
+async fn log_moderation(bot: &WBot, conf: &Conf, entry: LogEntry) {
+    use teloxide::utils::markdown::{bold, escape, link, user_mention};
+    let Some(log_chat) = conf.log_chat else {
+        return
+    };
+    let actor = user_name(bot, conf, entry.actor).await;
+    let mut lines = vec![
+        bold(&escape(entry.action)),
+        format!("Кто: {}", user_mention(entry.actor.0 as i64, &escape(&actor))),
+    ];
+    if let Some(target) = entry.target {
+        let name = user_name(bot, conf, target).await;
+        lines.push(format!("Кого: {}", user_mention(target.0 as i64, &escape(&name))));
+    }
+    if let Some(reason) = entry.reason {
+        lines.push(format!("Причина: {}", escape(&reason)));
+    }
+    if let Some(until) = entry.until {
+        lines.push(escape(&format!("До: {}", until.format("%d.%m.%Y %H:%M (UTC)"))));
+    }
+    if let Some(url) = entry.link {
+        lines.push(link(&url, "Ссылка"));
+    }
+    bot.send_message(log_chat, lines.join("\n")).parse_mode(ParseMode::MarkdownV2).await.ok_or_log();
+}
+
+/// Находит пользователя по упоминанию, id, @username или ссылке tg://user?id=.
+/// @username ищется среди админов канала и пользователей, которых бот видел после запуска
+async fn resolve_user(bot: &WBot, conf: &Conf, content: &Content) -> Option<UserId> {
+    let Content::Text(text) = content else {
+        return None
+    };
+    let mention = text.entities.iter().find_map(|entity|match &entity.kind {
+        teloxide::types::MessageEntityKind::TextMention { user } => Some(user.id),
+        _ => None,
+    });
+    if mention.is_some() {
+        return mention
+    }
+    match parse_user_ref(&text.text)? {
+        UserRef::Id(user_id) => Some(user_id),
+        UserRef::Username(username) => {
+            let is_same = |user: &teloxide::types::User| user.username.as_deref()
+                .map(|name|name.eq_ignore_ascii_case(&username))
+                .unwrap_or(false);
+            if let Some(user_id) = conf.known_user(&username) {
+                let member = bot.get_chat_member(conf.channel, user_id).await.ok_or_log()?;
+                return is_same(&member.user).then_some(user_id)
+            }
+            let admins = bot.get_chat_administrators(conf.channel).await.ok_or_log()?;
+            admins.into_iter().map(|member|member.user).find(is_same).map(|user|user.id)
+        }
+    }
+}
+
+/// Выдает капчу. Если пользователь не ответит вовремя, заявка на вступление будет отклонена
+async fn send_captcha(bot: &WBot, conf: &Conf, user_id: UserId, purpose: CaptchaPurpose) -> FSMResult {
+    let captcha = Captcha::random();
+    let markup = InlineKeyboardMarkup::new(vec![captcha.options.iter().enumerate()
+        .map(|(i, option)|InlineKeyboardButton::callback(option, CallbackResponse::Captcha(i).to_msg_text().unwrap()))
+        .collect::<Vec<_>>()
+    ]);
+    let text = format!("{}\n\n{}", conf.template(Template::CaptchaRequest), captcha.question);
+    let msg = bot.send_message(ChatId::from(user_id), text).reply_markup(markup).await?;
+    conf.add_captcha(user_id, PendingCaptcha { answer: captcha.answer, purpose, message: msg.id });
+    let (bot, conf) = (bot.clone(), conf.clone());
+    tokio::spawn(async move {
+        tokio::time::sleep(CAPTCHA_TIMEOUT).await;
+        let Some(captcha) = conf.take_expired_captcha(user_id, msg.id) else {
+            return
+        };
+        bot.edit_message_text(msg.chat.id, msg.id, conf.template(Template::CaptchaTimeout)).await.ok_or_log();
+        if let CaptchaPurpose::Join(chat_id) = captcha.purpose {
+            decline_unverified(&bot, &conf, user_id, chat_id, "капча не пройдена вовремя").await;
+        }
+    });
+    Ok(())
+}
+
+async fn decline_unverified(bot: &WBot, conf: &Conf, user_id: UserId, chat_id: ChatId, reason: &str) {
+    bot.decline_chat_join_request(chat_id, user_id).await.ok_or_log();
+    log_auto_decision(bot, conf, user_id, "Заявка на вступление отклонена автоматически", reason.to_owned()).await;
+}
+
+/// Отклоняет заявки, капча по которым осталась без ответа до перезапуска бота
+pub async fn expire_stale_captchas(bot: &WBot, conf: &Conf) {
+    for (user_id, chat_id, message) in conf.take_stale_captchas() {
+        bot.edit_message_text(ChatId::from(user_id), message, conf.template(Template::CaptchaTimeout)).await.ok_or_log();
+        decline_unverified(bot, conf, user_id, chat_id, "капча не пройдена до перезапуска бота").await;
+    }
+}
+
+fn join_request_markup(user_id: UserId, chat_id: ChatId) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Да", CallbackResponse::ApproveSubscribe(user_id, chat_id).to_msg_text().unwrap()),
+        InlineKeyboardButton::callback("Нет", CallbackResponse::DeclineSubscribe(user_id, chat_id).to_msg_text().unwrap()),
+        InlineKeyboardButton::callback("В бан", CallbackResponse::BanSubscribe(user_id, chat_id).to_msg_text().unwrap()),
+    ]])
+}
+
+async fn send_question(bot: &WBot, chat_id: ChatId, question: &persistent::Question) -> FSMResult {
+    let request = bot.send_message(chat_id, question.text.as_str());
+    if question.options.is_empty() {
+        request.await?;
+    } else {
+        let markup = InlineKeyboardMarkup::new(question.options.iter().enumerate().map(|(i, option)|{
+            vec![InlineKeyboardButton::callback(option, CallbackResponse::Answer(i).to_msg_text().unwrap())]
+        }));
+        request.reply_markup(markup).await?;
+    }
+    Ok(())
+}
+
+/// Записывает бан и банит пользователя в канале и группе обсуждения
+async fn ban_user(bot: &WBot, conf: &Conf, user_id: UserId, info: persistent::BanInfo) {
+    let until = info.until;
+    conf.ban(user_id, info);
+    ban_in_chats(bot, conf, user_id, until).await;
+}
+
+/// Банит (или меняет срок бана) во всех каналах бота и их группах обсуждения
+async fn ban_in_chats(bot: &WBot, conf: &Conf, user_id: UserId, until: Option<i64>) {
+    let until = until.and_then(|until|chrono::DateTime::from_timestamp(until, 0));
+    for chat in conf.channels().into_iter().chain(discussion_groups(bot, conf).await) {
+        let mut request = bot.ban_chat_member(chat, user_id);
+        if let Some(until) = until {
+            request = request.until_date(until);
+        }
+        request.await.ok_or_log();
+    }
+}
+
+/// Снимает бан в боте, каналах и группах обсуждения
+async fn unban_user(bot: &WBot, conf: &Conf, user_id: UserId) {
+    conf.unban(user_id);
+    for chat in conf.channels().into_iter().chain(discussion_groups(bot, conf).await) {
+        bot.unban_chat_member(chat, user_id).only_if_banned(true).await.ok_or_log();
+    }
+}
+
+async fn discussion_groups(bot: &WBot, conf: &Conf) -> Vec<ChatId> {
+    let mut groups = vec![];
+    for channel in conf.channels() {
+        if let Some(group) = bot.get_chat(channel).await.ok_or_log().and_then(|chat|chat.linked_chat_id()) {
+            groups.push(ChatId(group));
+        }
+    }
+    groups
+}
+
+/// Канал, к которому привязана группа обсуждения. Если не удалось узнать - основной канал
+async fn linked_channel(bot: &WBot, conf: &Conf, group: ChatId) -> ChatId {
+    if conf.routes.is_empty() {
+        return conf.channel
+    }
+    bot.get_chat(group).await.ok_or_log()
+        .and_then(|chat|chat.linked_chat_id()).map(ChatId)
+        .filter(|channel|conf.channels().contains(channel))
+        .unwrap_or(conf.channel)
+}
+
+async fn user_name(bot: &WBot, conf: &Conf, user_id: UserId) -> String {
+    bot.get_chat_member(conf.channel, user_id).await
+        .ok().map( |u|format!("{} {}", u.user.first_name, u.user.last_name.unwrap_or_default() ))
+        .unwrap_or(format!("[{}]", user_id))
+}
+
+async fn delete_msgs(bot: &WBot, channel: ChatId, ids: Vec<i32>) -> FSMResult {
+    for id in ids {
+        bot.delete_message(channel, MessageId(id)).await?;
+    }
+    Ok(())
+}
+
+async fn edit_callback_message(bot: WBot, upd: Update, text: &str) -> FSMResult {
+    if let UpdateKind::CallbackQuery(q) = upd.kind {
+        let msg = q.message.ok_or(anyhow!["Cannot invoke message from callback query"])?;
+        bot.edit_message_text(msg.chat.id, msg.id, text).await?;
+    } else {
+        bail!("Expects callback query, but not")
+    }
+    Ok(())
+}
+
+async fn edit_callback_reply_markup(bot: WBot, upd: Update, markup: InlineKeyboardMarkup) -> FSMResult {
+    if let UpdateKind::CallbackQuery(q) = upd.kind {
+        let msg = q.message.ok_or(anyhow!["Cannot invoke message from callback query"])?;
+        bot.edit_message_reply_markup(msg.chat.id, msg.id).reply_markup(markup).await?;
+    }
+    Ok(())
+}
+
+async fn edit_callback_markup(bot: WBot, upd: Update, text: &str, markup: InlineKeyboardMarkup) -> FSMResult {
+    if let UpdateKind::CallbackQuery(q) = upd.kind {
+        let msg = q.message.ok_or(anyhow!["Cannot invoke message from callback query"])?;
+        bot.edit_message_text(msg.chat.id, msg.id, text).reply_markup(markup).await?;
+    } else {
+        bail!("Expects callback query, but not")
+    }
+    Ok(())
+}
+
+//служебное сообщение удаляется вместе с командой, чтобы не засорять обсуждение
+async fn send_service_message(bot: WBot, msg: &GroupMessage, text: String) -> FSMResult {
+    let reply = bot.send_message(msg.chat_id, text).reply_to_message_id(msg.id).await?;
+    let chat_id = msg.chat_id;
+    let ids = [msg.id, reply.id];
+    tokio::spawn(async move {
+        tokio::time::sleep(SERVICE_MESSAGE_TTL).await;
+        for id in ids {
+            bot.delete_message(chat_id, id).await.ok_or_log();
+        }
+    });
+    Ok(())
+}
+
+/// Рассылает ссылку на новое объявление подписчикам его тегов и владельцам подходящих поисков
+fn notify_subscribers(bot: &WBot, conf: &Conf, ad: PublishedAd) {
+    use teloxide::utils::markdown::{escape, link};
+    let recipients: HashSet<_> = conf.subscribers(&ad.tags).into_iter()
+        .chain(conf.search_subscribers(&ad))
+        .filter(|user_id|*user_id != ad.user_id && conf.is_banned(user_id).is_none())
+        .collect();
+    let Some(url) = conf.post_url(&ad).filter(|_|!recipients.is_empty()) else {
+        return
+    };
+    let text = format!("{}\n{}\n{}",
+        escape(conf.template(Template::NewAdNotification)),
+        link(&url, &escape(&impls::ad_title(&ad))),
+        escape(&impls::ad_summary(&ad, conf)),
+    );
+    let (bot, conf) = (bot.clone(), conf.clone());
+    tokio::spawn(async move {
+        for user_id in recipients {
+            if conf.take_notification(user_id) {
+                bot.send_message(user_id, &text).parse_mode(ParseMode::MarkdownV2).await.ok_or_log();
+            }
+        }
+    });
+}
+
+/// Уведомляет о комментарии или ответе в обсуждении. Все, что пришло за `COMMENT_BATCH_DELAY`, приходит одним сообщением
+fn notify_comment(bot: &WBot, conf: &Conf, recipient: UserId, (channel, thread): (ChatId, i32), url: &str, template: Template) {
+    if conf.comments_muted(recipient, channel, thread) || !conf.add_to_comment_batch(recipient, channel, thread, url, template) {
+        return
+    }
+    let (bot, conf) = (bot.clone(), conf.clone());
+    tokio::spawn(async move {
+        tokio::time::sleep(COMMENT_BATCH_DELAY).await;
+        let Some(batch) = conf.take_comment_batch(recipient, channel, thread) else {
+            return
+        };
+        //уведомления могли выключить, пока копилась пачка
+        if conf.comments_muted(recipient, channel, thread) {
+            return
+        }
+        let text = if batch.count > 1 {
+            format!("{} {}", conf.template(Template::CommentsSummary), batch.count)
+        } else {
+            conf.template(batch.template).to_owned()
+        };
+        let text = impls::make_message_link(&text, &batch.url, Some(thread)).unwrap_or(text);
+        bot.send_message(recipient, text).parse_mode(ParseMode::MarkdownV2)
+            .reply_markup(comment_markup(&conf, recipient, channel, thread)).await.ok_or_log();
+    });
+}
+
+/// Обновляет все, что зависит от снятого объявления: каталог и избранное
+fn on_ad_removed(bot: &WBot, conf: &Conf, ad: &PublishedAd) {
+    catalog::update(bot, conf);
+    notify_favorites(bot, conf, ad, Template::FavoriteRemoved);
+}
+
+/// Сообщает о закрытии объявления тем, у кого оно в избранном, и убирает его из избранного
+fn notify_favorites(bot: &WBot, conf: &Conf, ad: &PublishedAd, template: Template) {
+    let users = conf.take_favorited_by(ad.channel, ad.post());
+    send_favorites_notice(bot, conf, ad, users, template);
+}
+
+fn send_favorites_notice(bot: &WBot, conf: &Conf, ad: &PublishedAd, users: Vec<UserId>, template: Template) {
+    if users.is_empty() {
+        return
+    }
+    let text = format!("{}\n{}\n{}", conf.template(template), impls::ad_title(ad), impls::ad_summary(ad, conf));
+    let bot = bot.clone();
+    tokio::spawn(async move {
+        for user_id in users {
+            bot.send_message(user_id, &text).await.ok_or_log();
+        }
+    });
+}
+
+/// Кнопки выключения уведомлений под уведомлением о комментарии
+fn comment_markup(conf: &Conf, user_id: UserId, channel: ChatId, thread: i32) -> InlineKeyboardMarkup {
+    let thread_text = if conf.comments_muted_exactly(user_id, Some((channel, thread))) {
+        "🔔 Снова уведомлять об этом объявлении"
+    } else {
+        "🔕 Не уведомлять об этом объявлении"
+    };
+    let all_text = if conf.comments_muted_exactly(user_id, None) {
+        "🔔 Включить все уведомления о комментариях"
+    } else {
+        "🔕 Выключить все уведомления о комментариях"
+    };
+    InlineKeyboardMarkup::new(vec![
+        vec![InlineKeyboardButton::callback(thread_text, CallbackResponse::ToggleThreadComments(channel, thread).to_msg_text().unwrap())],
+        vec![InlineKeyboardButton::callback(all_text, CallbackResponse::ToggleAllComments(Some((channel, thread))).to_msg_text().unwrap())],
+    ])
+}
+
 fn filter_private(u: Update) -> bool {
     u.chat().map(|c|c.is_private()).unwrap_or(false)
 }
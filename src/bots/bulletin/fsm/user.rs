@@ -1,46 +1,122 @@
 use super::*;
 use config::Template as Tpl;
+use crate::persistent::{Deal, Review};
 use teloxide::types::{MessageId, UpdateKind};
 
 const LINE_SIZE: usize = 3;
+const MAX_ANSWER_LEN: usize = 500;
+const MAX_REVIEW_LEN: usize = 300;
+const PROFILE_REVIEWS: usize = 5;
 
 pub fn process_user(handler: FSMHandler) -> FSMHandler {
     handler.branch(
         dptree::filter_map(Signal::filter_content)
         .branch(teloxide::handler![State::PriceWaitng(target)].endpoint(on_price_waiting))
         .branch(teloxide::handler![State::Filling(ad)].endpoint(on_filling))
+        .branch(teloxide::handler![State::WaitSavedSearch].endpoint(on_saved_search))
+        .branch(teloxide::handler![State::WaitBuyer(channel, post, title)].endpoint(on_wait_buyer))
+        .branch(teloxide::handler![State::WaitReview(channel, post, score)].endpoint(on_wait_review))
     ).branch(
         dptree::filter_map(Signal::filter_user_action)
         .endpoint(on_user_action)
     )
-    .branch(teloxide::handler![State::Subscribing(chat_id)].endpoint(on_subscribe_request))
+    .branch(teloxide::handler![State::Subscribing(chat_id, answers)].endpoint(on_subscribe_request))
+    .branch(teloxide::handler![State::Appealing(channel, post, notice)].endpoint(on_appeal))
 }
 
-async fn on_subscribe_request(
+async fn on_appeal(
     bot: WBot,
     dialogue: MyDialogue,
     conf: Conf,
     upd: Update,
-    chat_id: ChatId
+    (channel, post, notice): (ChatId, i32, String),
 ) -> FSMResult {
     if let UpdateKind::Message(msg) = upd.kind {
         dialogue.exit().await?;
-        let user_id = UserId(dialogue.chat_id().0 as u64);
-        let admins = conf.admins();
-        for (admin_id, _) in admins {
-            bot.forward_message(admin_id, dialogue.chat_id(), msg.id).await?;
-            let chat = bot.get_chat(chat_id).await?;
-            let msg = format!("Тут человек хочет вступить в {}. Пустить?", chat.title().unwrap_or("unknown"));
-            bot.send_message(admin_id, msg).reply_markup(
-                InlineKeyboardMarkup::new(vec![vec![
-                    InlineKeyboardButton::callback("Да", CallbackResponse::ApproveSubscribe(user_id, chat_id).to_msg_text().unwrap()),
-                    InlineKeyboardButton::callback("Нет", CallbackResponse::DeclineSubscribe(user_id, chat_id).to_msg_text().unwrap()),
-                    InlineKeyboardButton::callback("В бан", CallbackResponse::BanSubscribe(user_id, chat_id).to_msg_text().unwrap()),
-                ]])
-            ).await?;
+        let name = msg.from().map(make_username).unwrap_or_default();
+        let url = conf.message_url(channel, post);
+        let mut text = format!("{name} обжалует снятие объявления {}", url.unwrap_or_default());
+        if !notice.is_empty() {
+            text = format!("{text}\n\n{notice}");
+        }
+        for admin_id in conf.admins_with(roles::REMOVE_ADS) {
+            bot.send_message(admin_id, text.as_str()).disable_web_page_preview(true).await.ok_or_log();
+            bot.forward_message(admin_id, dialogue.chat_id(), msg.id).await.ok_or_log();
+        }
+        bot.send_message(dialogue.chat_id(), conf.template(Tpl::AppealSent)).await?;
+    }
+    Ok(())
+}
+
+async fn on_subscribe_request(
+    bot: WBot,
+    dialogue: MyDialogue,
+    conf: Conf,
+    upd: Update,
+    (chat_id, mut answers): (ChatId, Vec<String>),
+) -> FSMResult {
+    let UpdateKind::Message(msg) = upd.kind else {
+        return Ok(())
+    };
+    let name = msg.from().map(make_username).unwrap_or_default();
+    let Some(question) = conf.questions.get(answers.len()) else {
+        // анкеты нет - пересылаем админам само сообщение
+        dialogue.exit().await?;
+        return send_join_request(&bot, &conf, dialogue.user_id(), chat_id, "человек", Some(msg.id), None).await
+    };
+    if !question.options.is_empty() {
+        bot.send_message(dialogue.chat_id(), conf.template(Tpl::ChooseOption)).await?;
+    } else if let Some(text) = msg.text() {
+        answers.push(text.chars().take(MAX_ANSWER_LEN).collect());
+        next_question(&bot, &conf, &dialogue, &name, chat_id, answers).await?;
+    } else {
+        bot.send_message(dialogue.chat_id(), conf.template(Tpl::AnswerWithText)).await?;
+    }
+    Ok(())
+}
+
+/// Задает следующий вопрос анкеты, а после последнего отправляет заявку админам
+async fn next_question(bot: &WBot, conf: &Conf, dialogue: &MyDialogue, name: &str, chat_id: ChatId, answers: Vec<String>) -> FSMResult {
+    if let Some(question) = conf.questions.get(answers.len()) {
+        send_question(bot, dialogue.chat_id(), question).await?;
+        dialogue.update(State::Subscribing(chat_id, answers)).await?;
+        return Ok(())
+    }
+    dialogue.exit().await?;
+    let card = conf.questions.iter().zip(answers)
+        .map(|(question, answer)|format!("{}\n— {answer}", question.text))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    send_join_request(bot, conf, dialogue.user_id(), chat_id, name, None, Some(card)).await
+}
+
+/// Отправляет админам карточку заявки с кнопками решения
+async fn send_join_request(
+    bot: &WBot,
+    conf: &Conf,
+    user_id: UserId,
+    chat_id: ChatId,
+    name: &str,
+    forward: Option<MessageId>,
+    answers: Option<String>,
+) -> FSMResult {
+    let chat = bot.get_chat(chat_id).await?;
+    let mut text = format!("Тут {name} хочет вступить в {}. Пустить?", chat.title().unwrap_or("unknown"));
+    if let Some(answers) = answers {
+        text = format!("{text}\n\n{answers}");
+    }
+    let markup = join_request_markup(user_id, chat_id);
+    let mut cards = vec![];
+    for admin_id in conf.admins_with(roles::APPROVE_JOINS) {
+        if let Some(msg_id) = forward {
+            bot.forward_message(admin_id, ChatId::from(user_id), msg_id).await.ok_or_log();
+        }
+        if let Some(card) = bot.send_message(admin_id, text.as_str()).reply_markup(markup.clone()).await.ok_or_log() {
+            cards.push((card.chat.id, card.id));
         }
-        bot.send_message(dialogue.chat_id(), conf.template(Tpl::RequestForwarded)).await?;
     }
+    conf.add_join_request(user_id, chat_id, text, cards);
+    bot.send_message(ChatId::from(user_id), conf.template(Tpl::RequestForwarded)).await?;
     Ok(())
 }
 
@@ -91,6 +167,191 @@ fn tags_markup(ad: &Ad, tags: &[String], message_id: i32) -> InlineKeyboardMarku
     InlineKeyboardMarkup::new(btns)
 }
 
+async fn on_saved_search(
+    bot: WBot,
+    dialogue: MyDialogue,
+    content: Content,
+    conf: Conf,
+) -> FSMResult {
+    let chat_id = dialogue.chat_id();
+    let Content::Text(text) = content else {
+        bot.send_message(chat_id, conf.template(Tpl::WrongMessage)).await?;
+        return Ok(())
+    };
+    if AdQuery::parse(&text.text).is_empty() {
+        bot.send_message(chat_id, conf.template(Tpl::EmptySearch)).await?;
+        return Ok(())
+    }
+    dialogue.exit().await?;
+    let user_id = dialogue.user_id();
+    let text = match conf.add_saved_search(user_id, text.text.trim().to_owned()) {
+        Some(_) => conf.template(Tpl::SearchSaved),
+        None => conf.template(Tpl::TooManySearches),
+    };
+    bot.send_message(chat_id, text).reply_markup(saved_searches_markup(&conf, user_id)).await?;
+    Ok(())
+}
+
+async fn on_wait_buyer(
+    bot: WBot,
+    dialogue: MyDialogue,
+    conf: Conf,
+    upd: Update,
+    content: Content,
+    (channel, post, title): (ChatId, i32, String),
+) -> FSMResult {
+    let chat_id = dialogue.chat_id();
+    let seller = dialogue.user_id();
+    let forwarded = match upd.kind {
+        UpdateKind::Message(msg) => msg.forward_from_user().map(|user|user.id),
+        _ => None,
+    };
+    let buyer = match forwarded {
+        Some(buyer) => Some(buyer),
+        None => resolve_user(&bot, &conf, &content).await,
+    };
+    let Some(buyer) = buyer.filter(|buyer|*buyer != seller) else {
+        bot.send_message(chat_id, conf.template(Tpl::BuyerNotFound)).reply_markup(skip_markup(CallbackResponse::SkipBuyer)).await?;
+        return Ok(())
+    };
+    dialogue.exit().await?;
+    //оценки откроются только после подтверждения покупателем, иначе можно оценить кого угодно
+    conf.add_deal(Deal { channel, post, seller, buyer, title: title.clone(), confirmed: false });
+    let text = format!("{}\n{}", conf.template(Tpl::ConfirmDeal), title);
+    let markup = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Подтверждаю", CallbackResponse::ConfirmDeal(channel, post).to_msg_text()?),
+        InlineKeyboardButton::callback("Не покупал", CallbackResponse::RejectDeal(channel, post).to_msg_text()?),
+    ]]);
+    if let Err(e) = bot.send_message(buyer, text).reply_markup(markup).await {
+        log::warn!("cannot ask buyer {} to confirm a deal: {}", buyer, e);
+        conf.reject_deal(channel, post, buyer);
+        bot.send_message(chat_id, conf.template(Tpl::BuyerNotReachable)).await?;
+        return Ok(())
+    }
+    bot.send_message(chat_id, conf.template(Tpl::DealPending)).await?;
+    Ok(())
+}
+
+async fn on_wait_review(
+    bot: WBot,
+    dialogue: MyDialogue,
+    conf: Conf,
+    content: Content,
+    (channel, post, score): (ChatId, i32, u8),
+) -> FSMResult {
+    let Some(text) = content.text() else {
+        bot.send_message(dialogue.chat_id(), conf.template(Tpl::AnswerWithText)).await?;
+        return Ok(())
+    };
+    dialogue.exit().await?;
+    save_review(&bot, &conf, dialogue.user_id(), (channel, post), score, text.trim().chars().take(MAX_REVIEW_LEN).collect()).await
+}
+
+/// Второй участник подтвержденной сделки, если пользователь в ней участвовал
+fn counterpart(deal: &Deal, user_id: UserId) -> Option<UserId> {
+    if !deal.confirmed {
+        None
+    } else if user_id == deal.seller {
+        Some(deal.buyer)
+    } else if user_id == deal.buyer {
+        Some(deal.seller)
+    } else {
+        None
+    }
+}
+
+async fn save_review(bot: &WBot, conf: &Conf, author: UserId, (channel, post): (ChatId, i32), score: u8, text: String) -> FSMResult {
+    let Some(target) = conf.deal(channel, post).and_then(|deal|counterpart(&deal, author)) else {
+        return Ok(())
+    };
+    let review = Review { channel, post, author, target, score: score.into(), text, created: chrono::Utc::now().timestamp() };
+    let reply = if conf.add_review(review) {
+        Tpl::ReviewSaved
+    } else {
+        Tpl::AlreadyReviewed
+    };
+    bot.send_message(author, conf.template(reply)).reply_markup(conf.keyboard(author)).await?;
+    Ok(())
+}
+
+fn rating_markup(channel: ChatId, post: i32) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![(1..=5).map(|score|
+        InlineKeyboardButton::callback(format!("{score}⭐"), CallbackResponse::Rate(channel, post, score).to_msg_text().unwrap())
+    ).collect::<Vec<_>>()])
+}
+
+fn skip_markup(response: CallbackResponse) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback("Пропустить", response.to_msg_text().unwrap())]])
+}
+
+/// Средняя оценка пользователя и последние отзывы о нем
+fn profile_text(conf: &Conf, user_id: UserId, name: &str) -> String {
+    let Some((score, count)) = conf.rating(user_id) else {
+        return format!("{name}\n{}", conf.template(Tpl::NoReviews))
+    };
+    let reviews = conf.reviews_of(user_id, PROFILE_REVIEWS).into_iter().map(|review|{
+        let date = chrono::DateTime::from_timestamp(review.created, 0)
+            .map(|date|date.format("%d.%m.%Y").to_string())
+            .unwrap_or_default();
+        let stars = "⭐".repeat(review.score as usize);
+        if review.text.is_empty() {
+            format!("{stars} {date}")
+        } else {
+            format!("{stars} {date}\n{}", review.text)
+        }
+    });
+    std::iter::once(format!("{name}\n⭐ {score:.1} ({count})")).chain(reviews).collect::<Vec<_>>().join("\n\n")
+}
+
+/// Список избранного со ссылками и кнопками удаления
+fn favorites_message(conf: &Conf, user_id: UserId) -> (String, InlineKeyboardMarkup) {
+    use teloxide::utils::markdown::escape;
+    let ads = conf.favorites(user_id);
+    if ads.is_empty() {
+        return (escape(conf.template(Tpl::FavoritesEmpty)), InlineKeyboardMarkup::default())
+    }
+    let lines: Vec<_> = ads.iter().filter_map(|ad|impls::ad_list_item(ad, conf)).collect();
+    let text = format!("{}\n{}", escape(conf.template(Tpl::FavoritesInfo)), lines.join("\n"));
+    let btns = ads.iter().map(|ad|{
+        vec![InlineKeyboardButton::callback(format!("❌ {}", impls::ad_title(ad)), CallbackResponse::RemoveFavorite(ad.channel, ad.post()).to_msg_text().unwrap())]
+    });
+    (text, InlineKeyboardMarkup::new(btns))
+}
+
+fn saved_searches_markup(conf: &Conf, user_id: UserId) -> InlineKeyboardMarkup {
+    let mut btns: Vec<_> = conf.saved_searches(user_id).into_iter().map(|search|{
+        let mark = if search.paused { "⏸" } else { "🔍" };
+        vec![
+            InlineKeyboardButton::callback(format!("{mark} {}", search.query), CallbackResponse::ToggleSearch(search.id).to_msg_text().unwrap()),
+            InlineKeyboardButton::callback("❌", CallbackResponse::DeleteSearch(search.id).to_msg_text().unwrap()),
+        ]
+    }).collect();
+    btns.push(vec![InlineKeyboardButton::callback("➕ Добавить поиск", CallbackResponse::AddSearch.to_msg_text().unwrap())]);
+    InlineKeyboardMarkup::new(btns)
+}
+
+fn subscriptions_markup(conf: &Conf, user_id: UserId) -> InlineKeyboardMarkup {
+    let subscribed = conf.subscriptions(user_id);
+    let mut btns: Vec<Vec<_>> = conf.tags.chunks(LINE_SIZE).map(|line|line.iter().map(|tag|{
+        let mark = if subscribed.contains(tag) { "✅" } else { "☑️" };
+        let data = CallbackResponse::ToggleSubscription(tag.clone()).to_msg_text().unwrap();
+        InlineKeyboardButton::callback(format!("{mark} {tag}"), data)
+    }).collect()).collect();
+    let mute = if conf.notifications_muted(user_id) {
+        "🔕 Уведомления выключены"
+    } else {
+        "🔔 Уведомления включены"
+    };
+    btns.push(vec![InlineKeyboardButton::callback(mute, CallbackResponse::ToggleNotifications.to_msg_text().unwrap())]);
+    let comments = if conf.comments_muted_exactly(user_id, None) {
+        "🔕 Уведомления о комментариях выключены"
+    } else {
+        "💬 Уведомления о комментариях включены"
+    };
+    btns.push(vec![InlineKeyboardButton::callback(comments, CallbackResponse::ToggleAllComments(None).to_msg_text().unwrap())]);
+    InlineKeyboardMarkup::new(btns)
+}
+
 async fn on_filling(
     bot: WBot,
     dialogue: MyDialogue,
@@ -126,24 +387,52 @@ async fn on_user_action(
         },
         UserAction::Create => {
             check_is_member(bot.clone(), conf.clone(), chat_id).await?;
-            dialogue.update(State::ActionWaiting).await?;
-            let callback = InlineKeyboardButton::callback;
-            bot.send_message(chat_id, conf.template(Tpl::RequestTarget))
-            .reply_markup(InlineKeyboardMarkup::new( vec![
-                vec![
-                    callback(conf.template(Template::WantBuy), CallbackResponse::Target(Target::Buy).to_msg_text().unwrap()),
-                    callback(conf.template(Template::WantSell), CallbackResponse::Target(Target::Sell).to_msg_text().unwrap()),
-                ],
-                vec![
-                    callback(conf.template(Template::WantAsk), CallbackResponse::Target(Target::Ask).to_msg_text().unwrap()),
-                    callback(conf.template(Template::WantRecommend), CallbackResponse::Target(Target::Recommend).to_msg_text().unwrap()),
-                ]
-            ])).await?;
+            if conf.needs_captcha(&user_id) && conf.captcha_locked(&user_id) {
+                bot.send_message(chat_id, conf.template(Tpl::CaptchaLocked)).await?;
+            } else if conf.needs_captcha(&user_id) {
+                send_captcha(&bot, &conf, user_id, CaptchaPurpose::Create).await?;
+            } else {
+                start_create(&bot, &conf, &dialogue).await?;
+            }
+        },
+        UserAction::Captcha(i) => {
+            let Some(captcha) = conf.take_captcha(user_id) else {
+                edit_callback_message(bot, upd, conf.template(Tpl::CaptchaTimeout)).await?;
+                return Ok(())
+            };
+            if i == captcha.answer {
+                let user = upd.user().cloned().ok_or(anyhow!("Cannot invoke user from captcha callback"))?;
+                conf.verify(user_id);
+                edit_callback_message(bot.clone(), upd, conf.template(Tpl::CaptchaPassed)).await?;
+                match captcha.purpose {
+                    CaptchaPurpose::Join(subscription_chat) => {
+                        admit_join_request(&bot, &conf, &dialogue, &user, subscription_chat).await?;
+                    }
+                    CaptchaPurpose::Create => start_create(&bot, &conf, &dialogue).await?,
+                }
+            } else {
+                edit_callback_message(bot.clone(), upd, conf.template(Tpl::CaptchaFailed)).await?;
+                match captcha.purpose {
+                    CaptchaPurpose::Join(subscription_chat) => {
+                        decline_unverified(&bot, &conf, user_id, subscription_chat, "неверный ответ на капчу").await;
+                    }
+                    CaptchaPurpose::Create => if conf.captcha_failed(user_id) {
+                        bot.send_message(chat_id, conf.template(Tpl::CaptchaLocked)).await?;
+                    } else {
+                        send_captcha(&bot, &conf, user_id, CaptchaPurpose::Create).await?;
+                    },
+                }
+            }
         },
         UserAction::Publish => on_publish(bot, conf, dialogue).await?,
         UserAction::Yes => if let State::Preview(ad) = dialogue.get_or_default().await? {
-            let msgs: Vec<_> = send_ad(bot.clone(), conf.clone(), conf.channel, user_id, &ad).await?;
+            let channel = conf.destination(&ad);
+            let msgs: Vec<_> = send_ad(bot.clone(), conf.clone(), channel, user_id, &ad).await?;
             dialogue.exit().await?;
+            let published = ad.published(channel, user_id, &msgs);
+            conf.add_ad(published.clone());
+            notify_subscribers(&bot, &conf, published);
+            catalog::update(&bot, &conf);
             let ids: Vec<_> = msgs.iter().map(|m|m.id.0).collect();
             let data = CallbackResponse::Remove(ids).to_msg_text()?;
             let msg = msgs.first().ok_or(anyhow!("Published msgs is empty"))?;
@@ -152,6 +441,10 @@ async fn on_user_action(
             .unwrap_or(conf.template(Tpl::Published).into());
             let mut markup = InlineKeyboardMarkup::default()
                 .append_row(vec![InlineKeyboardButton::callback(conf.template(Tpl::RemoveAd), data)]);
+            if ad.target == Target::Sell {
+                let data = CallbackResponse::Sold(channel, msg.id.0).to_msg_text()?;
+                markup = markup.append_row(vec![InlineKeyboardButton::callback(conf.template(Tpl::SoldButton), data)]);
+            }
             if conf.donate_enabled() {
                 markup = markup.append_row(vec![CONF.tip_button()]);
             }
@@ -161,9 +454,32 @@ async fn on_user_action(
             dialogue.update(State::Filling(ad)).await?;
             bot.send_message(chat_id, conf.template(Tpl::ContinueFilling)).await?;
         },
+        UserAction::Answer(i) => if let State::Subscribing(subscription_chat, mut answers) = dialogue.get_or_default().await? {
+            let question = conf.questions.get(answers.len()).ok_or(anyhow!("no question for answer {i}"))?;
+            let option = question.options.get(i).ok_or(anyhow!("unknown option {i}"))?.clone();
+            let name = upd.user().map(make_username).unwrap_or_default();
+            edit_callback_message(bot.clone(), upd, &format!("{}\n— {option}", question.text)).await?;
+            answers.push(option);
+            next_question(&bot, &conf, &dialogue, &name, subscription_chat, answers).await?;
+        },
+        UserAction::Appeal(channel, post) => {
+            let notice = match &upd.kind {
+                UpdateKind::CallbackQuery(q) => q.message.as_ref().and_then(|msg|msg.text()).unwrap_or_default().to_owned(),
+                _ => String::new(),
+            };
+            dialogue.update(State::Appealing(channel, post, notice)).await?;
+            bot.send_message(chat_id, conf.template(Tpl::AppealRequest)).await?;
+        },
         UserAction::Remove(msgs) => {
-            let text = match delete_msgs(&bot, msgs, &conf).await {
-                Ok(_) => conf.template(Tpl::AdRemoved),
+            let ad = msgs.first().and_then(|&post|conf.find_user_ad(user_id, post));
+            let channel = ad.as_ref().map(|ad|ad.channel).unwrap_or(conf.channel);
+            let text = match delete_msgs(&bot, channel, msgs).await {
+                Ok(_) => {
+                    if let Some(ad) = ad.and_then(|ad|conf.remove_ad(ad.channel, ad.post())) {
+                        on_ad_removed(&bot, &conf, &ad);
+                    }
+                    conf.template(Tpl::AdRemoved)
+                },
                 Err(e) => {
                     log::error!("Err on remove ad: {:?}", e);
                     conf.template(Tpl::CannotRemoveAd)
@@ -195,6 +511,132 @@ async fn on_user_action(
             dialogue.update(State::Filling(ad)).await?;
             bot.edit_message_reply_markup(dialogue.chat_id(), MessageId(message_id)).reply_markup(markup).await?;
         },
+        UserAction::Subscriptions => {
+            bot.send_message(chat_id, conf.template(Tpl::SubscriptionsInfo))
+                .reply_markup(subscriptions_markup(&conf, user_id)).await?;
+        },
+        UserAction::ToggleSubscription(tag) => if conf.tags.contains(&tag) {
+            conf.toggle_subscription(user_id, tag);
+            edit_callback_markup(bot, upd, conf.template(Tpl::SubscriptionsInfo), subscriptions_markup(&conf, user_id)).await?;
+        },
+        UserAction::ToggleNotifications => {
+            conf.toggle_notifications(user_id);
+            edit_callback_markup(bot, upd, conf.template(Tpl::SubscriptionsInfo), subscriptions_markup(&conf, user_id)).await?;
+        },
+        UserAction::ToggleThreadComments(channel, thread) => {
+            conf.toggle_comments(user_id, Some((channel, thread)));
+            edit_callback_reply_markup(bot, upd, comment_markup(&conf, user_id, channel, thread)).await?;
+        },
+        UserAction::ToggleAllComments(thread) => {
+            conf.toggle_comments(user_id, None);
+            match thread {
+                Some((channel, thread)) => edit_callback_reply_markup(bot, upd, comment_markup(&conf, user_id, channel, thread)).await?,
+                None => edit_callback_markup(bot, upd, conf.template(Tpl::SubscriptionsInfo), subscriptions_markup(&conf, user_id)).await?,
+            }
+        },
+        UserAction::Favorites => {
+            let (text, markup) = favorites_message(&conf, user_id);
+            bot.send_message(chat_id, text).parse_mode(ParseMode::MarkdownV2)
+                .disable_web_page_preview(true).reply_markup(markup).await?;
+        },
+        UserAction::AddFavorite(channel, post) => {
+            let text = match conf.find_channel_ad(channel, post) {
+                Some(ad) => {
+                    conf.add_favorite(user_id, ad.channel, ad.post());
+                    conf.template(Tpl::FavoriteAdded)
+                },
+                None => conf.template(Tpl::FavoriteNotFound),
+            };
+            bot.send_message(chat_id, text).reply_markup(conf.keyboard(user_id)).await?;
+        },
+        UserAction::RemoveFavorite(channel, post) => {
+            conf.remove_favorite(user_id, channel, post);
+            let (text, markup) = favorites_message(&conf, user_id);
+            if let UpdateKind::CallbackQuery(q) = upd.kind {
+                let msg = q.message.ok_or(anyhow!["Cannot invoke message from callback query"])?;
+                bot.edit_message_text(msg.chat.id, msg.id, text).parse_mode(ParseMode::MarkdownV2)
+                    .disable_web_page_preview(true).reply_markup(markup).await?;
+            }
+        },
+        UserAction::Sold(channel, post) => {
+            let Some(ad) = conf.find_channel_ad(channel, post).filter(|ad|ad.user_id == user_id) else {
+                edit_callback_message(bot, upd, conf.template(Tpl::AdNotFound)).await?;
+                return Ok(())
+            };
+            if let Err(e) = delete_msgs(&bot, ad.channel, ad.messages.clone()).await {
+                log::error!("Err on remove sold ad: {:?}", e);
+                if let UpdateKind::CallbackQuery(ref q) = upd.kind {
+                    bot.answer_callback_query(q.id.clone()).text(conf.template(Tpl::CannotRemoveAd)).await?;
+                }
+                return Ok(())
+            }
+            conf.remove_ad(ad.channel, ad.post());
+            catalog::update(&bot, &conf);
+            notify_favorites(&bot, &conf, &ad, Tpl::FavoriteSold);
+            edit_callback_message(bot.clone(), upd, conf.template(Tpl::AdSold)).await?;
+            dialogue.update(State::WaitBuyer(ad.channel, ad.post(), impls::ad_title(&ad))).await?;
+            bot.send_message(chat_id, conf.template(Tpl::AskBuyer)).reply_markup(skip_markup(CallbackResponse::SkipBuyer)).await?;
+        },
+        UserAction::SkipBuyer => if let State::WaitBuyer(..) = dialogue.get_or_default().await? {
+            dialogue.exit().await?;
+            edit_callback_reply_markup(bot, upd, InlineKeyboardMarkup::default()).await?;
+        },
+        UserAction::Rate(channel, post, score) => {
+            let is_participant = conf.deal(channel, post).and_then(|deal|counterpart(&deal, user_id)).is_some();
+            if !is_participant || !(1..=5).contains(&score) {
+                return Ok(())
+            }
+            if conf.has_review(channel, post, user_id) {
+                edit_callback_message(bot, upd, conf.template(Tpl::AlreadyReviewed)).await?;
+                return Ok(())
+            }
+            dialogue.update(State::WaitReview(channel, post, score)).await?;
+            let text = format!("{}\n{}", "⭐".repeat(score.into()), conf.template(Tpl::ReviewRequest));
+            edit_callback_markup(bot, upd, &text, skip_markup(CallbackResponse::SkipReview)).await?;
+        },
+        UserAction::SkipReview => if let State::WaitReview(channel, post, score) = dialogue.get_or_default().await? {
+            dialogue.exit().await?;
+            edit_callback_reply_markup(bot.clone(), upd, InlineKeyboardMarkup::default()).await?;
+            save_review(&bot, &conf, user_id, (channel, post), score, String::new()).await?;
+        },
+        UserAction::ConfirmDeal(channel, post) => {
+            let Some(deal) = conf.confirm_deal(channel, post, user_id) else {
+                edit_callback_reply_markup(bot, upd, InlineKeyboardMarkup::default()).await?;
+                return Ok(())
+            };
+            let text = format!("{}\n{}", conf.template(Tpl::RateSeller), deal.title);
+            edit_callback_markup(bot.clone(), upd, &text, rating_markup(channel, post)).await?;
+            bot.send_message(deal.seller, conf.template(Tpl::RateBuyer)).reply_markup(rating_markup(channel, post)).await?;
+        },
+        UserAction::RejectDeal(channel, post) => {
+            let Some(deal) = conf.reject_deal(channel, post, user_id) else {
+                edit_callback_reply_markup(bot, upd, InlineKeyboardMarkup::default()).await?;
+                return Ok(())
+            };
+            edit_callback_message(bot.clone(), upd, conf.template(Tpl::DealDeclined)).await?;
+            let text = format!("{}\n{}", conf.template(Tpl::DealRejected), deal.title);
+            bot.send_message(deal.seller, text).await?;
+        },
+        UserAction::Profile(target) => {
+            let name = user_name(&bot, &conf, target).await;
+            bot.send_message(chat_id, profile_text(&conf, target, name.trim())).reply_markup(conf.keyboard(user_id)).await?;
+        },
+        UserAction::SavedSearches => {
+            bot.send_message(chat_id, conf.template(Tpl::SavedSearchesInfo))
+                .reply_markup(saved_searches_markup(&conf, user_id)).await?;
+        },
+        UserAction::AddSearch => {
+            dialogue.update(State::WaitSavedSearch).await?;
+            bot.send_message(chat_id, conf.template(Tpl::SearchRequest)).await?;
+        },
+        UserAction::ToggleSearch(search_id) => {
+            conf.toggle_saved_search(user_id, search_id);
+            edit_callback_markup(bot, upd, conf.template(Tpl::SavedSearchesInfo), saved_searches_markup(&conf, user_id)).await?;
+        },
+        UserAction::DeleteSearch(search_id) => {
+            conf.delete_saved_search(user_id, search_id);
+            edit_callback_markup(bot, upd, conf.template(Tpl::SavedSearchesInfo), saved_searches_markup(&conf, user_id)).await?;
+        },
         UserAction::RemoveTag(tag, message_id) => if let State::Filling(mut ad) = dialogue.get_or_default().await? {
             ad.tags.remove(&tag);
             let markup = tags_markup(&ad, &conf.tags, message_id);
@@ -205,17 +647,35 @@ async fn on_user_action(
     Ok(())
 }
 
-async fn delete_msgs(bot: &WBot, ids: Vec<i32>, conf: &Conf) -> FSMResult {
-    for id in ids {
-        bot.delete_message(conf.channel, MessageId(id)).await?;
-    }
+async fn start_create(bot: &WBot, conf: &Conf, dialogue: &MyDialogue) -> FSMResult {
+    dialogue.update(State::ActionWaiting).await?;
+    let callback = InlineKeyboardButton::callback;
+    bot.send_message(dialogue.chat_id(), conf.template(Tpl::RequestTarget))
+    .reply_markup(InlineKeyboardMarkup::new( vec![
+        vec![
+            callback(conf.template(Template::WantBuy), CallbackResponse::Target(Target::Buy).to_msg_text().unwrap()),
+            callback(conf.template(Template::WantSell), CallbackResponse::Target(Target::Sell).to_msg_text().unwrap()),
+        ],
+        vec![
+            callback(conf.template(Template::WantAsk), CallbackResponse::Target(Target::Ask).to_msg_text().unwrap()),
+            callback(conf.template(Template::WantRecommend), CallbackResponse::Target(Target::Recommend).to_msg_text().unwrap()),
+        ]
+    ])).await?;
     Ok(())
 }
 
+/// Объявление может уйти в любой из каналов бота, поэтому достаточно подписки на один из них
 async fn check_is_member(bot: WBot, conf: Conf, chat_id: ChatId) -> FSMResult {
     let user_id = UserId(u64::try_from(chat_id.0)?);
-    let chat_member = bot.get_chat_member(conf.channel, user_id).await?;
-    if conf.only_subscribers() && (chat_member.is_left() || chat_member.is_banned()) {
+    let mut is_member = false;
+    for channel in conf.channels() {
+        let chat_member = bot.get_chat_member(channel, user_id).await?;
+        if !chat_member.is_left() && !chat_member.is_banned() {
+            is_member = true;
+            break
+        }
+    }
+    if conf.only_subscribers() && !is_member {
         if conf.approve_subscribe() {
             let invite = bot.create_chat_invite_link(conf.channel)
                 .creates_join_request(true)
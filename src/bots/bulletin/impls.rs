@@ -1,7 +1,14 @@
 use super::*;
-use teloxide::types::{ChatId, User, InputFile, ParseMode, InputMedia, InputMediaPhoto, UserId};
+use teloxide::types::{ChatId, User, InputFile, ParseMode, InputMedia, InputMediaPhoto, UserId, InlineKeyboardButton, InlineKeyboardMarkup};
 use teloxide::utils::markdown::*;
 use num_format::{Locale, ToFormattedString};
+use crate::persistent::PublishedAd;
+use crate::impls::LoggableErrorResult;
+use teloxide::payloads::EditMessageCaptionSetters;
+
+const LIST_TITLE_LEN: usize = 50;
+/// Ограничение Telegram на длину подписи к фото после разбора разметки
+const MAX_CAPTION_LEN: usize = 1024;
 
 fn make_ad_text(user: &User, ad: &Ad, conf: Conf) -> String {
     let user_id = user.id.0.try_into().unwrap();
@@ -17,13 +24,53 @@ fn make_ad_text(user: &User, ad: &Ad, conf: Conf) -> String {
         Target::Recommend => format!("\\#{}", conf.template(Template::RecommendText)),
     };
     let full_name = escape(&user.full_name());
-    let sign = user_mention(user_id, &full_name);
+    let mut sign = user_mention(user_id, &full_name);
+    if let Some((score, count)) = conf.rating(user.id) {
+        let rating = escape(&format!("⭐ {:.1} ({})", score, count));
+        let rating = match conf.profile_url(user.id) {
+            Some(url) => link(&url, &rating),
+            None => rating,
+        };
+        sign = format!("{} {}", sign, rating);
+    }
     let tags = ad.tags.iter().fold(String::new(), |sum, tag|{
         sum + " \\#" + tag
     });
     format!("{}\n{}\n\n{}\n\n{}\n",user_link + &text, tags, price, sign)
 }
 
+/// Краткое описание опубликованного объявления без разметки: цель, цена и теги
+pub fn ad_summary(ad: &PublishedAd, conf: &Config) -> String {
+    let price = ad.price.to_formatted_string(&Locale::ru);
+    let target = match Target::from_index(ad.target) {
+        Some(Target::Buy) => format!("{} {} {}", conf.template(Template::BuyText), price, conf.template(Template::Currency)),
+        Some(Target::Sell) => format!("{} {} {}", conf.template(Template::SellText), price, conf.template(Template::Currency)),
+        Some(Target::Ask) => conf.template(Template::AskText).to_owned(),
+        Some(Target::Recommend) => conf.template(Template::RecommendText).to_owned(),
+        None => String::new(),
+    };
+    ad.tags.iter().fold(target, |sum, tag|sum + " #" + tag)
+}
+
+/// Предел длины сообщения со списком объявлений: запас до лимита Телеграм в 4096 символов
+pub const MAX_LIST_MESSAGE_LEN: usize = 3800;
+
+/// Заголовок объявления для списков и уведомлений: первая непустая строка, обрезанная до LIST_TITLE_LEN
+pub fn ad_title(ad: &PublishedAd) -> String {
+    ad.text.lines().find(|l|!l.trim().is_empty()).unwrap_or("…").chars().take(LIST_TITLE_LEN).collect()
+}
+
+/// Строка списка объявлений: ссылка на пост и цена, в разметке MarkdownV2
+pub fn ad_list_item(ad: &PublishedAd, conf: &Config) -> Option<String> {
+    let url = conf.post_url(ad)?;
+    let mut line = format!("• {}", link(&url, &escape(&ad_title(ad))));
+    if Target::from_index(ad.target).is_some_and(|t|t.has_price()) {
+        let price = ad.price.to_formatted_string(&Locale::ru);
+        line += &escape(&format!(" — {} {}", price, conf.template(Template::Currency)));
+    }
+    Some(line)
+}
+
 pub fn make_message_link(text: &str, url: &str, thread: Option<i32>) -> Option<String> {
     let text = escape(text);
     let mut words: Vec<_> = text.split(" ").collect();
@@ -38,21 +85,43 @@ pub fn make_message_link(text: &str, url: &str, thread: Option<i32>) -> Option<S
 }
 
 pub async fn send_ad(bot: WBot, conf: Conf, target_chat_id: ChatId, user_id: UserId, ad: &Ad) -> Result<Vec<Message>> {
-    let chat_member = bot.get_chat_member(conf.channel, user_id).await?;
+    let member_chat = if target_chat_id.is_user() { conf.channel } else { target_chat_id };
+    let chat_member = bot.get_chat_member(member_chat, user_id).await?;
     let user = chat_member.user;
-    let text = make_ad_text(&user, ad, conf);
+    let text = make_ad_text(&user, ad, conf.clone());
     let bot = bot.parse_mode(ParseMode::MarkdownV2);
     let mut photos: Vec<_> = ad.photos.iter().map(make_photo).collect();
+    let to_channel = !target_chat_id.is_user() && conf.bot_username().is_some();
     let msgs = if photos.is_empty() {
-        vec![bot.send_message(target_chat_id, text).await?]
+        let request = bot.send_message(target_chat_id, text);
+        let msg = if to_channel {
+            let button = InlineKeyboardButton::callback(conf.template(Template::FavoriteLink), CallbackResponse::Favorite.to_msg_text()?);
+            request.reply_markup(InlineKeyboardMarkup::new(vec![vec![button]])).await?
+        } else {
+            request.await?
+        };
+        vec![msg]
     } else {
-        photos.first_mut().map(|photo|{
+        if let Some(photo) = photos.first_mut() {
             photo.caption = Some(text);
             photo.parse_mode = Some(ParseMode::MarkdownV2);
-        });
-        let media: Vec<_> = photos.into_iter().map(|p|InputMedia::Photo(p)).collect();
+        }
+        let media: Vec<_> = photos.into_iter().map(InputMedia::Photo).collect();
         bot.send_media_group(target_chat_id, media).await?
     };
+    //у альбома не бывает кнопок, поэтому ссылка дописывается в подпись, когда id поста уже известен
+    if to_channel && !ad.photos.is_empty() {
+        if let Some((msg, url)) = msgs.first().and_then(|msg|Some((msg, conf.favorite_url(target_chat_id, msg.id.0)?))) {
+            let label = conf.template(Template::FavoriteLink);
+            let caption_len = msg.caption().unwrap_or_default().chars().count() + 1 + label.chars().count();
+            if caption_len <= MAX_CAPTION_LEN {
+                let text = format!("{}\n{}", make_ad_text(&user, ad, conf.clone()), link(&url, &escape(label)));
+                bot.edit_message_caption(target_chat_id, msg.id).caption(text).await.ok_or_log();
+            } else {
+                log::info!("No room for favorite link in caption of post {}", msg.id.0);
+            }
+        }
+    }
     Ok(msgs)
 }
 
@@ -18,6 +18,9 @@ mod fsm;
 mod entity;
 mod impls;
 mod ad;
+mod captcha;
+mod catalog;
+mod digest;
 mod config;
 
 mod res;
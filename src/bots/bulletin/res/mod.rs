@@ -3,7 +3,30 @@ pub const CREATE: &str = "Новое объявление";
 pub const PUBLISH: &str = "Опубликовать";
 pub const BAN: &str = "Забанить";
 pub const UNBAN: &str = "Амнистировать";
+pub const WARN: &str = "Предупредить";
+pub const TAKEDOWN: &str = "Снять объявление";
 pub const ADD_ADMIN: &str = "Добавить админа";
 pub const REMOVE_ADMIN: &str = "Разжаловать";
+pub const SUBSCRIPTIONS: &str = "Подписки";
+pub const SAVED_SEARCHES: &str = "Поиски";
+pub const FAVORITES: &str = "Избранное";
+/// Параметр /start для добавления объявления в избранное: `fav<id поста>`
+pub const FAVORITE_START: &str = "fav";
+/// Параметр /start для просмотра оценок пользователя: `profile<id пользователя>`
+pub const PROFILE_START: &str = "profile";
+
+pub const TAKEDOWN_REASONS: [&str; 4] = [
+    "Нарушение правил канала",
+    "Подозрение на мошенничество",
+    "Дубликат объявления",
+    "Неактуально",
+];
+
+/// Варианты продления бана: надпись и срок в днях
+pub const BAN_EXTENSIONS: [(&str, i64); 3] = [
+    ("+1 день", 1),
+    ("+1 неделя", 7),
+    ("+1 месяц", 30),
+];
 
 pub const HELP: &str = include_str!("help.txt");
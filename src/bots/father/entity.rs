@@ -1,6 +1,7 @@
 use serde::{Serialize, Deserialize};
 
 use crate::bots::CallbackMessage;
+use crate::bots::roles::Role;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum CallbackResponse {
@@ -19,6 +20,29 @@ pub enum CallbackResponse {
     ToggleOption(i32),
     Back,
     Save,
+    WarnLimit,
+    LogChat,
+    ResetLogChat,
+    Admins,
+    AddAdmin,
+    SelectAdmin(u64),
+    SetRole(u64, Role),
+    DeleteAdmin(u64),
+    BlacklistAdd,
+    BlacklistSearch,
+    BlacklistEntry(u64),
+    BlacklistRemove(u64),
+    Questionnaire,
+    AddQuestion,
+    RemoveQuestion(i64),
+    Allowlist,
+    AllowlistAdd,
+    AllowlistRemove(u64),
+    Routes,
+    AddRoute,
+    RemoveRoute(i64),
+    RouteTarget(i64),
+    ConfirmRemoveQuestion(i64),
 }
 
 impl CallbackMessage for CallbackResponse {}
\ No newline at end of file
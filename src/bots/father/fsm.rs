@@ -10,6 +10,7 @@ use super::WrappedBot as WBot;
 use super::entity::CallbackResponse;
 use crate::bots::bulletin::{Config as RunnableConfig, Template};
 use crate::bots::flags::*;
+use crate::bots::roles::Role;
 
 type MyDialogue = Dialogue<State, MyStorage>;
 pub type FSMResult = Result<()>;
@@ -23,6 +24,8 @@ const FORWARD: &str = "Отлично! Теперь нужно добавить
 const NOT_FORWARDED_FROM_CHANNEL: &str = "Это не то. Нужно переслать сообщение из канала"; 
 const CHOOSE_THE_BOT: &str = "Выбери бота:";
 const INVALID_TOKEN: &str = "Неверный токен. Попробуй другой";
+//в том же порядке, что и цели объявлений в базе
+const ROUTE_TARGETS: [&str; 4] = ["покупка", "продажа", "вопрос", "рекомендация"];
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "These commands are supported:")]
@@ -35,6 +38,8 @@ enum Command {
     MyBots,
     #[command(description = "удалить бота")]
     Delete,
+    #[command(description = "общий черный список")]
+    Blacklist,
     //Теперь команды для гобального админа
     #[command(description = "уведомить пользователей о новых фичах")]
     PublishInfo(String),
@@ -42,12 +47,13 @@ enum Command {
     Stop,
 }
 
+/// Команды для всех пользователей: черный список и команды глобального админа в меню не попадают
 pub fn bot_commands() -> Vec<BotCommand> {
     Command::bot_commands().into_iter().take(4).collect()
 }
 
 use super::flags::Flags;
-use crate::persistent::BulletinConfig; //TODO: надо разобраться с наименованиями
+use crate::persistent::{BulletinConfig, BlacklistEntry, Question, Route}; //TODO: надо разобраться с наименованиями
 
 #[derive(Clone, Debug)]
 pub enum State {
@@ -59,6 +65,17 @@ pub enum State {
     UpdatingToken(i64, String),
     WaitTag(i64, String),
     EditOptions(i64, String, Flags),
+    WaitWarnLimit(i64, String),
+    WaitLogChat(i64, String),
+    WaitAdminForward(i64, String),
+    WaitBlacklistUser,
+    WaitBlacklistCause(u64, String),
+    WaitBlacklistSearch,
+    WaitQuestion(i64, String),
+    WaitAllowlistUser(i64, String),
+    WaitRouteChannel(i64, String),
+    /// Канал выбран, ждем тег или цель объявлений для него
+    WaitRouteRule(i64, String, i64),
 }
 
 impl Default for State {
@@ -76,7 +93,17 @@ pub fn make_dialogue_handler() -> FSMHandler {
         .branch(handler!(WaitForward(token)).endpoint(wait_forward) )
         .branch(handler!(WaitText(bot_id,name,template_id)).endpoint(on_wait_template))
         .branch(handler!(WaitTag(bot_id,name)).endpoint(on_wait_tag))
-        .branch(handler!(UpdatingToken(bot_id, name)).endpoint(on_update_token));
+        .branch(handler!(UpdatingToken(bot_id, name)).endpoint(on_update_token))
+        .branch(handler!(WaitWarnLimit(bot_id, name)).endpoint(on_wait_warn_limit))
+        .branch(handler!(WaitLogChat(bot_id, name)).endpoint(on_wait_log_chat))
+        .branch(handler!(WaitAdminForward(bot_id, name)).endpoint(on_wait_admin_forward))
+        .branch(handler!(WaitBlacklistUser).endpoint(on_wait_blacklist_user))
+        .branch(handler!(WaitBlacklistCause(user_id, name)).endpoint(on_wait_blacklist_cause))
+        .branch(handler!(WaitBlacklistSearch).endpoint(on_wait_blacklist_search))
+        .branch(handler!(WaitQuestion(bot_id, name)).endpoint(on_wait_question))
+        .branch(handler!(WaitAllowlistUser(bot_id, name)).endpoint(on_wait_allowlist_user))
+        .branch(handler!(WaitRouteChannel(bot_id, name)).endpoint(on_wait_route_channel))
+        .branch(handler!(WaitRouteRule(bot_id, name, channel)).endpoint(on_wait_route_rule));
     let callback_handler = Update::filter_callback_query()
         .branch(handler!(EditOptions(id,name,flags )).endpoint(on_edit_options))
         .branch(handler!(Changing(id, name)).endpoint(on_changing_callback))
@@ -103,6 +130,12 @@ fn markup_edit_bot() -> InlineKeyboardMarkup {
         vec![callback("Удалить тег",        RemoveTag.to_msg_text().unwrap()    )],
         vec![callback("Обновить токен",     UpdateToken.to_msg_text().unwrap()  )],
         vec![callback("Опции",              Options.to_msg_text().unwrap()      )],
+        vec![callback("Предупреждения",     WarnLimit.to_msg_text().unwrap()    )],
+        vec![callback("Лог модерации",      LogChat.to_msg_text().unwrap()      )],
+        vec![callback("Админы",             Admins.to_msg_text().unwrap()       )],
+        vec![callback("Анкета вступления",  Questionnaire.to_msg_text().unwrap())],
+        vec![callback("Белый список",       Allowlist.to_msg_text().unwrap()    )],
+        vec![callback("Каналы по тегам",    Routes.to_msg_text().unwrap()       )],
         vec![CONF.tip_button()],
     ])
 }
@@ -148,6 +181,72 @@ async fn on_wait_tag(bot: WBot, dialogue: MyDialogue, (bot_id, bot_name): (i64,
     Ok(())
 }
 
+async fn on_wait_question(bot: WBot, dialogue: MyDialogue, (bot_id, bot_name): (i64, String), db: DBStorage, msg: Message) -> FSMResult {
+    let text = msg.text().ok_or(anyhow!("No text on wait question"))?;
+    let mut lines = text.lines().map(str::trim).filter(|line|!line.is_empty());
+    let Some(question) = lines.next() else {
+        bot.send_message(dialogue.chat_id(), "Пустой вопрос не годится").await?;
+        return Ok(())
+    };
+    let options: Vec<_> = lines.map(ToOwned::to_owned).collect();
+    db.add_question(bot_id, Question { text: question.to_owned(), options }).await;
+    dialogue.update(State::Changing(bot_id, bot_name.clone())).await?;
+    bot.send_message(dialogue.chat_id(), format!("Вопрос добавлен (для вступления в силу нужен рестарт бота)\nВыбран бот @{}\nЧто будем делать?", bot_name))
+        .reply_markup(markup_edit_bot()).await?;
+    Ok(())
+}
+
+async fn on_wait_allowlist_user(bot: WBot, dialogue: MyDialogue, (bot_id, bot_name): (i64, String), db: DBStorage, msg: Message) -> FSMResult {
+    let user = msg.forward_from_user().map(|user|(user.id.0, make_username(user)))
+        .or_else(||msg.text().and_then(|text|text.trim().parse().ok()).map(|id|(id, format!("[{id}]"))));
+    let Some((user_id, name)) = user else {
+        bot.send_message(dialogue.chat_id(), "Это не то. Нужно переслать сообщение от пользователя или прислать его id").await?;
+        return Ok(())
+    };
+    db.add_to_allowlist(bot_id, user_id as i64, name.clone()).await;
+    dialogue.update(State::Changing(bot_id, bot_name.clone())).await?;
+    bot.send_message(dialogue.chat_id(), format!("{name} добавлен в белый список (для вступления в силу нужен рестарт бота)\nВыбран бот @{bot_name}\nЧто будем делать?"))
+        .reply_markup(markup_edit_bot()).await?;
+    Ok(())
+}
+
+async fn on_wait_route_channel(bot: WBot, dialogue: MyDialogue, (bot_id, bot_name): (i64, String), msg: Message) -> FSMResult {
+    let forwarded_chat = msg.forward_from_chat().filter(|chat|chat.is_channel()).map(|chat|chat.id);
+    let channel = forwarded_chat.or_else(||msg.text().and_then(|text|text.trim().parse().ok()).map(ChatId));
+    let Some(channel) = channel else {
+        bot.send_message(dialogue.chat_id(), NOT_FORWARDED_FROM_CHANNEL).await?;
+        return Ok(())
+    };
+    dialogue.update(State::WaitRouteRule(bot_id, bot_name, channel.0)).await?;
+    let targets = ROUTE_TARGETS.iter().enumerate()
+        .map(|(i, name)|vec![InlineKeyboardButton::callback(*name, CallbackResponse::RouteTarget(i as i64).to_msg_text().unwrap())]);
+    bot.send_message(dialogue.chat_id(), "Присылай тег, объявления с которым пойдут в этот канал, или выбери цель объявлений")
+        .reply_markup(with_back_button(InlineKeyboardMarkup::new(targets))).await?;
+    Ok(())
+}
+
+async fn on_wait_route_rule(bot: WBot, dialogue: MyDialogue, (bot_id, bot_name, channel): (i64, String, i64), db: DBStorage, msg: Message) -> FSMResult {
+    let text = msg.text().ok_or(anyhow!("No text on wait route rule"))?;
+    let tag = text.trim().trim_start_matches('#');
+    if !db.get_tags(bot_id).await.iter().any(|t|t == tag) {
+        bot.send_message(dialogue.chat_id(), "У бота нет такого тега. Присылай один из тегов бота или выбери цель").await?;
+        return Ok(())
+    }
+    db.add_route(bot_id, Route { channel: ChatId(channel), tag: Some(tag.to_owned()), target: None }).await;
+    dialogue.update(State::Changing(bot_id, bot_name.clone())).await?;
+    bot.send_message(dialogue.chat_id(), format!("Правило добавлено (для вступления в силу нужен рестарт бота)\nВыбран бот @{bot_name}\nЧто будем делать?"))
+        .reply_markup(markup_edit_bot()).await?;
+    Ok(())
+}
+
+/// Описание правила для списка в настройках: канал и условие
+fn route_caption(route: &Route) -> String {
+    let tag = route.tag.as_ref().map(|tag|format!("#{tag}"));
+    let target = route.target.and_then(|t|ROUTE_TARGETS.get(t as usize)).map(|t|t.to_string());
+    let rule = tag.into_iter().chain(target).collect::<Vec<_>>().join(", ");
+    format!("{rule} → {}", route.channel)
+}
+
 async fn on_update_token(bot: WBot, dialogue: MyDialogue, (bot_id, bot_name): (i64, String), db: DBStorage, msg: Message) -> FSMResult {
     let token = msg.text().ok_or(anyhow!("No text on wait text"))?;
     db.update_token(bot_id, token.to_owned()).await;
@@ -159,6 +258,120 @@ async fn on_update_token(bot: WBot, dialogue: MyDialogue, (bot_id, bot_name): (i
     Ok(())
 }
 
+async fn on_wait_warn_limit(bot: WBot, dialogue: MyDialogue, (bot_id, bot_name): (i64, String), db: DBStorage, msg: Message) -> FSMResult {
+    let text = msg.text().ok_or(anyhow!("No text on wait warn limit"))?;
+    let limit = match text.trim().parse::<usize>() {
+        Ok(limit) if limit > 0 => limit,
+        _ => {
+            bot.send_message(dialogue.chat_id(), "Нужно прислать число больше нуля").await?;
+            return Ok(())
+        }
+    };
+    db.update_warn_limit(bot_id, limit).await;
+    dialogue.update(State::Changing(bot_id, bot_name.clone())).await?;
+    bot.send_message(
+        dialogue.chat_id(), 
+        format!("Лимит предупреждений обновлен (для вступления в силу нужен рестарт бота)\nВыбран бот @{}\nЧто будем делать?", bot_name)
+    ).reply_markup(markup_edit_bot()).await?;
+    Ok(())
+}
+
+async fn on_wait_log_chat(bot: WBot, dialogue: MyDialogue, (bot_id, bot_name): (i64, String), db: DBStorage, msg: Message) -> FSMResult {
+    let forwarded_chat = msg.forward_from_chat().filter(|chat|chat.is_channel()).map(|chat|chat.id);
+    let log_chat = forwarded_chat.or_else(||msg.text().and_then(|text|text.trim().parse().ok()).map(ChatId));
+    let Some(log_chat) = log_chat else {
+        bot.send_message(dialogue.chat_id(), "Это не то. Нужно переслать сообщение из канала или прислать id чата").await?;
+        return Ok(())
+    };
+    db.update_log_chat(bot_id, Some(log_chat)).await;
+    dialogue.update(State::Changing(bot_id, bot_name.clone())).await?;
+    bot.send_message(
+        dialogue.chat_id(), 
+        format!("Лог модерации настроен (для вступления в силу нужен рестарт бота)\nВыбран бот @{}\nЧто будем делать?", bot_name)
+    ).reply_markup(markup_edit_bot()).await?;
+    Ok(())
+}
+
+fn markup_admin_roles(user_id: u64) -> InlineKeyboardMarkup {
+    use CallbackResponse::*;
+    let roles = Role::ALL.into_iter()
+        .map(|role|InlineKeyboardButton::callback(role.name(), SetRole(user_id, role).to_msg_text().unwrap()))
+        .collect();
+    with_back_button(InlineKeyboardMarkup::new(vec![
+        roles,
+        vec![InlineKeyboardButton::callback("Удалить", DeleteAdmin(user_id).to_msg_text().unwrap())],
+    ]))
+}
+
+/// Проверяет, что после изменения у бота останется хотя бы один владелец
+async fn keeps_owner(db: &DBStorage, bot_id: i64, user_id: u64) -> bool {
+    let admins = db.get_admins(bot_id).await;
+    admins.iter().any(|(id, _, role)|*role == Role::Owner && id.0 != user_id)
+}
+
+async fn on_wait_admin_forward(bot: WBot, dialogue: MyDialogue, (bot_id, bot_name): (i64, String), db: DBStorage, msg: Message) -> FSMResult {
+    let Some(admin) = msg.forward_from_user() else {
+        bot.send_message(dialogue.chat_id(), "Это не то. Нужно переслать любое сообщение от человека, которого ты хочешь сделать админом").await?;
+        return Ok(())
+    };
+    let name = make_username(admin);
+    if !db.get_admins(bot_id).await.iter().any(|(id, ..)|*id == admin.id) {
+        db.add_admin(bot_id, admin.id.0 as i64, name.clone(), Role::Admin).await;
+    }
+    dialogue.update(State::Changing(bot_id, bot_name)).await?;
+    bot.send_message(dialogue.chat_id(), format!("{name} теперь админ. Можешь поменять роль (для вступления в силу нужен рестарт бота)"))
+        .reply_markup(markup_admin_roles(admin.id.0)).await?;
+    Ok(())
+}
+
+fn markup_blacklist() -> InlineKeyboardMarkup {
+    use CallbackResponse::*;
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Добавить", BlacklistAdd.to_msg_text().unwrap()),
+        InlineKeyboardButton::callback("Найти", BlacklistSearch.to_msg_text().unwrap()),
+    ]])
+}
+
+async fn can_edit_blacklist(db: &DBStorage, user_id: UserId) -> bool {
+    CONF.is_global_admin(user_id) || (CONF.owners_blacklist && !db.get_bots(user_id.0 as i64).await.is_empty())
+}
+
+async fn on_wait_blacklist_user(bot: WBot, dialogue: MyDialogue, msg: Message) -> FSMResult {
+    let user = msg.forward_from_user().map(|user|(user.id.0, make_username(user)))
+        .or_else(||msg.text().and_then(|text|text.trim().parse().ok()).map(|id|(id, format!("[{id}]"))));
+    if let Some((user_id, name)) = user {
+        dialogue.update(State::WaitBlacklistCause(user_id, name.clone())).await?;
+        bot.send_message(dialogue.chat_id(), format!("Пиши причину, по которой {name} попадет в черный список")).await?;
+    } else {
+        bot.send_message(dialogue.chat_id(), "Это не то. Нужно переслать сообщение от пользователя или прислать его id").await?;
+    }
+    Ok(())
+}
+
+async fn on_wait_blacklist_cause(bot: WBot, dialogue: MyDialogue, (user_id, name): (u64, String), db: DBStorage, msg: Message) -> FSMResult {
+    let cause = msg.text().ok_or(anyhow!("No text on wait blacklist cause"))?;
+    let entry = BlacklistEntry { name: name.clone(), cause: cause.to_owned(), added_by: dialogue.user_id() };
+    db.add_to_blacklist(UserId(user_id), entry).await;
+    dialogue.exit().await?;
+    bot.send_message(dialogue.chat_id(), format!("{name} добавлен в черный список всех барахолок")).await?;
+    Ok(())
+}
+
+async fn on_wait_blacklist_search(bot: WBot, dialogue: MyDialogue, db: DBStorage, msg: Message) -> FSMResult {
+    let query = msg.text().ok_or(anyhow!("No text on wait blacklist search"))?;
+    let found = db.blacklist().search(query);
+    if found.is_empty() {
+        bot.send_message(dialogue.chat_id(), "Никого не нашел, попробуй иначе").await?;
+        return Ok(())
+    }
+    let buttons: Vec<_> = found.into_iter().take(20).map(|(user_id, entry)|vec![InlineKeyboardButton::callback(
+        format!("{} ({})", entry.name, entry.cause),
+        CallbackResponse::BlacklistEntry(user_id.0).to_msg_text().unwrap()
+    )]).collect();
+    bot.send_message(dialogue.chat_id(), "Вот кого нашел:").reply_markup(InlineKeyboardMarkup::new(buttons)).await?;
+    Ok(())
+}
+
 async fn on_wait_template(bot: WBot, dialogue: MyDialogue, 
     (bot_id, name, template_id): (i64, String, usize), 
     msg: Message, db: DBStorage) -> FSMResult {
@@ -197,6 +410,66 @@ fn markup_options(flags: flags::Flags) -> InlineKeyboardMarkup {
             format!("Подписка через бота {}", status(APPROVE_SUBSCRIBE)),
             ToggleOption(APPROVE_SUBSCRIBE).to_msg_text().unwrap()
         )],
+        vec![callback(
+            format!("Бан вместо мьюта после предупреждений {}", status(WARN_BAN)),
+            ToggleOption(WARN_BAN).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Автоодобрение: есть username {}", status(AUTO_APPROVE_USERNAME)),
+            ToggleOption(AUTO_APPROVE_USERNAME).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Автоодобрение: публиковал объявления {}", status(AUTO_APPROVE_AUTHORS)),
+            ToggleOption(AUTO_APPROVE_AUTHORS).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Автоодобрение: белый список {}", status(AUTO_APPROVE_ALLOWLIST)),
+            ToggleOption(AUTO_APPROVE_ALLOWLIST).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Автоотказ забаненным {}", status(AUTO_DECLINE_BANNED)),
+            ToggleOption(AUTO_DECLINE_BANNED).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Антиспам: ссылки {}", status(SPAM_LINKS)),
+            ToggleOption(SPAM_LINKS).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Антиспам: ссылки-приглашения {}", status(SPAM_INVITES)),
+            ToggleOption(SPAM_INVITES).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Антиспам: репосты из каналов {}", status(SPAM_FORWARDS)),
+            ToggleOption(SPAM_FORWARDS).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Антиспам: много упоминаний {}", status(SPAM_MENTIONS)),
+            ToggleOption(SPAM_MENTIONS).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Антиспам: повторы {}", status(SPAM_REPEATS)),
+            ToggleOption(SPAM_REPEATS).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Антиспам: мьют нарушителя {}", status(SPAM_MUTE)),
+            ToggleOption(SPAM_MUTE).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Капча для новых пользователей {}", status(CAPTCHA)),
+            ToggleOption(CAPTCHA).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Ежедневный дайджест в канале {}", status(DAILY_DIGEST)),
+            ToggleOption(DAILY_DIGEST).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Еженедельный дайджест в канале {}", status(WEEKLY_DIGEST)),
+            ToggleOption(WEEKLY_DIGEST).to_msg_text().unwrap()
+        )],
+        vec![callback(
+            format!("Закрепленный каталог объявлений {}", status(CATALOG)),
+            ToggleOption(CATALOG).to_msg_text().unwrap()
+        )],
         vec![callback("OK".to_owned(), Save.to_msg_text().unwrap())],
     ])
 }
@@ -298,6 +571,144 @@ async fn on_changing_callback(bot: WBot, dialogue: MyDialogue, callback: Callbac
             bot.edit_message_text(dialogue.chat_id(), message_id, format!("Можешь настроить опции для бота {bot_name}"))
                 .reply_markup(markup_options(cfg.flags)).await?;
         }
+        WarnLimit => {
+            let cfg = db.get_config(bot_id).await.ok_or(anyhow!("bot with id {bot_id} not found"))?;
+            dialogue.update(State::WaitWarnLimit(bot_id, bot_name)).await?;
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                format!("Сейчас после {} предупреждений пользователь получает мьют или бан (см. опции). Присылай новый лимит", cfg.warn_limit)
+            ).reply_markup(with_back_button(InlineKeyboardMarkup::default())).await?;
+        },
+        LogChat => {
+            dialogue.update(State::WaitLogChat(bot_id, bot_name.clone())).await?;
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                format!("Все действия админов @{bot_name} будут записываться в отдельный чат. \
+                Добавь туда бота и пересылай сообщение из этого канала или присылай id группы")
+            ).reply_markup(with_back_button(InlineKeyboardMarkup::new(vec![vec![
+                InlineKeyboardButton::callback("Отключить лог", ResetLogChat.to_msg_text().unwrap())
+            ]]))).await?;
+        },
+        Admins => {
+            let mut buttons: Vec<_> = db.get_admins(bot_id).await.into_iter().map(|(id, name, role)|{
+                vec![InlineKeyboardButton::callback(format!("{name} ({})", role.name()), SelectAdmin(id.0).to_msg_text().unwrap())]
+            }).collect();
+            buttons.push(vec![InlineKeyboardButton::callback("Добавить админа", AddAdmin.to_msg_text().unwrap())]);
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                "Владелец управляет ботом и админами, только ему доступны настройки здесь. Админ банит, принимает заявки и удаляет объявления, модератор только банит и удаляет объявления"
+            ).reply_markup(with_back_button(InlineKeyboardMarkup::new(buttons))).await?;
+        },
+        AddAdmin => {
+            dialogue.update(State::WaitAdminForward(bot_id, bot_name)).await?;
+            bot.edit_message_text(dialogue.chat_id(), message_id, "Пересылай сообщение от человека - сделаем его админом")
+                .reply_markup(with_back_button(InlineKeyboardMarkup::default())).await?;
+        },
+        SelectAdmin(user_id) => {
+            let admins = db.get_admins(bot_id).await;
+            let (_, name, role) = admins.iter().find(|(id, ..)|id.0 == user_id)
+                .ok_or(anyhow!("admin {user_id} not found for bot {bot_id}"))?;
+            bot.edit_message_text(dialogue.chat_id(), message_id, format!("{name} сейчас {}. Выбери новую роль", role.name()))
+                .reply_markup(markup_admin_roles(user_id)).await?;
+        },
+        SetRole(user_id, role) => {
+            let admins = db.get_admins(bot_id).await;
+            let (_, name, _) = admins.iter().find(|(id, ..)|id.0 == user_id)
+                .ok_or(anyhow!("admin {user_id} not found for bot {bot_id}"))?;
+            let text = if role != Role::Owner && !keeps_owner(&db, bot_id, user_id).await {
+                "Нельзя оставить бота без владельца".to_owned()
+            } else {
+                db.add_admin(bot_id, user_id as i64, name.clone(), role).await;
+                format!("{name} теперь {} (для вступления в силу нужен рестарт бота)", role.name())
+            };
+            bot.edit_message_text(dialogue.chat_id(), message_id, format!("{text}\nВыбран бот @{bot_name}\nЧто будем делать?"))
+                .reply_markup(markup_edit_bot()).await?;
+        },
+        DeleteAdmin(user_id) => {
+            let text = if keeps_owner(&db, bot_id, user_id).await {
+                db.remove_admin(bot_id, user_id as i64).await;
+                "Админ удален (для вступления в силу нужен рестарт бота)"
+            } else {
+                "Нельзя оставить бота без владельца"
+            };
+            bot.edit_message_text(dialogue.chat_id(), message_id, format!("{text}\nВыбран бот @{bot_name}\nЧто будем делать?"))
+                .reply_markup(markup_edit_bot()).await?;
+        },
+        Questionnaire => {
+            let mut buttons: Vec<_> = db.get_questions(bot_id).await.into_iter().map(|(id, question)|{
+                let text = if question.options.is_empty() {
+                    question.text
+                } else {
+                    format!("{} ({})", question.text, question.options.join(" / "))
+                };
+                vec![InlineKeyboardButton::callback(text, RemoveQuestion(id).to_msg_text().unwrap())]
+            }).collect();
+            buttons.push(vec![InlineKeyboardButton::callback("Добавить вопрос", AddQuestion.to_msg_text().unwrap())]);
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                "Вопросы задаются по порядку тем, кто подал заявку на вступление (нужна опция подтверждения вступления). \
+                Нажми на вопрос, чтобы удалить его"
+            ).reply_markup(with_back_button(InlineKeyboardMarkup::new(buttons))).await?;
+        },
+        AddQuestion => {
+            dialogue.update(State::WaitQuestion(bot_id, bot_name)).await?;
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                "Присылай вопрос. Если нужен выбор из вариантов, перечисли их в следующих строках, по одному на строку"
+            ).reply_markup(with_back_button(InlineKeyboardMarkup::default())).await?;
+        },
+        RemoveQuestion(id) => {
+            let question = db.get_questions(bot_id).await.into_iter().find(|(question_id, _)|*question_id == id)
+                .ok_or(anyhow!("question {id} not found"))?.1;
+            bot.edit_message_text(dialogue.chat_id(), message_id, format!("Удалить вопрос «{}»?", question.text))
+                .reply_markup(with_back_button(InlineKeyboardMarkup::new(vec![vec![
+                    InlineKeyboardButton::callback("Удалить", ConfirmRemoveQuestion(id).to_msg_text().unwrap())
+                ]]))).await?;
+        },
+        ConfirmRemoveQuestion(id) => {
+            db.delete_question(bot_id, id).await;
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                format!("Вопрос удален (для вступления в силу нужен рестарт бота)\nВыбран бот @{bot_name}\nЧто будем делать?")
+            ).reply_markup(markup_edit_bot()).await?;
+        },
+        Allowlist => {
+            let mut buttons: Vec<_> = db.get_allowlist(bot_id).await.into_iter().map(|(id, name)|{
+                vec![InlineKeyboardButton::callback(name, AllowlistRemove(id.0).to_msg_text().unwrap())]
+            }).collect();
+            buttons.push(vec![InlineKeyboardButton::callback("Добавить", AllowlistAdd.to_msg_text().unwrap())]);
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                "Заявки на вступление от пользователей из белого списка принимаются автоматически (включается в опциях). \
+                Нажми на пользователя, чтобы убрать его из списка"
+            ).reply_markup(with_back_button(InlineKeyboardMarkup::new(buttons))).await?;
+        },
+        AllowlistAdd => {
+            dialogue.update(State::WaitAllowlistUser(bot_id, bot_name)).await?;
+            bot.edit_message_text(dialogue.chat_id(), message_id, "Пересылай сообщение от пользователя или присылай его id")
+                .reply_markup(with_back_button(InlineKeyboardMarkup::default())).await?;
+        },
+        AllowlistRemove(user_id) => {
+            db.remove_from_allowlist(bot_id, user_id as i64).await;
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                format!("Пользователь убран из белого списка (для вступления в силу нужен рестарт бота)\nВыбран бот @{bot_name}\nЧто будем делать?")
+            ).reply_markup(markup_edit_bot()).await?;
+        },
+        Routes => {
+            let mut buttons: Vec<_> = db.get_routes(bot_id).await.into_iter().map(|(id, route)|{
+                vec![InlineKeyboardButton::callback(route_caption(&route), RemoveRoute(id).to_msg_text().unwrap())]
+            }).collect();
+            buttons.push(vec![InlineKeyboardButton::callback("Добавить канал", AddRoute.to_msg_text().unwrap())]);
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                "Объявления с выбранным тегом или целью публикуются в отдельный канал, остальные - в основной. \
+                Правила проверяются по порядку. Нажми на правило, чтобы удалить его"
+            ).reply_markup(with_back_button(InlineKeyboardMarkup::new(buttons))).await?;
+        },
+        AddRoute => {
+            dialogue.update(State::WaitRouteChannel(bot_id, bot_name)).await?;
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                "Добавь бота в админы канала, чтобы он мог постить туда объявления, и пересылай сообщение оттуда или присылай id канала"
+            ).reply_markup(with_back_button(InlineKeyboardMarkup::default())).await?;
+        },
+        RemoveRoute(id) => {
+            db.delete_route(bot_id, id).await;
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                format!("Правило удалено (для вступления в силу нужен рестарт бота)\nВыбран бот @{bot_name}\nЧто будем делать?")
+            ).reply_markup(markup_edit_bot()).await?;
+        },
         Nothing => {},
         Back => {
             bot.edit_message_text(dialogue.chat_id(), message_id, format!("Выбран бот @{}\nЧто будем делать?", bot_name))
@@ -336,12 +747,36 @@ async fn on_callback(bot: WBot, dialogue: MyDialogue, callback: CallbackQuery, d
                     .reply_markup(markup_edit_bot()).await?;
             }
         },
+        RouteTarget(target) => {
+            if let Some(State::WaitRouteRule(bot_id, name, channel)) = dialogue.get().await? {
+                db.add_route(bot_id, Route { channel: ChatId(channel), tag: None, target: Some(target) }).await;
+                dialogue.update(State::Changing(bot_id, name.clone())).await?;
+                bot.edit_message_text(dialogue.chat_id(), message_id, 
+                    format!("Правило добавлено (для вступления в силу нужен рестарт бота)\nВыбран бот @{name}\nЧто будем делать?")
+                ).reply_markup(markup_edit_bot()).await?;
+            }
+        },
+        ResetLogChat => {
+            if let Some(State::WaitLogChat(bot_id, name)) = dialogue.get().await? {
+                dialogue.update(State::Changing(bot_id, name.clone())).await?;
+                db.update_log_chat(bot_id, None).await;
+                bot.send_message(dialogue.chat_id(), format!("Лог модерации отключен (для вступления в силу нужен рестарт бота)\nВыбран бот @{}\nЧто будем делать?", name))
+                    .reply_markup(markup_edit_bot()).await?;
+            }
+        },
         Back => {
             match dialogue.get_or_default().await? {
                 State::Changing(id, name) |
                 State::WaitText(id, name, _) |
                 State::UpdatingToken(id, name) |
-                State::WaitTag(id, name) => {
+                State::WaitTag(id, name) |
+                State::WaitWarnLimit(id, name) |
+                State::WaitLogChat(id, name) |
+                State::WaitAdminForward(id, name) |
+                State::WaitQuestion(id, name) |
+                State::WaitAllowlistUser(id, name) |
+                State::WaitRouteChannel(id, name) |
+                State::WaitRouteRule(id, name, _) => {
                     dialogue.update(State::Changing(id, name.clone())).await?;
                     bot.edit_message_text(dialogue.chat_id(), message_id, format!("Выбран бот @{}\nЧто будем делать?", name))
                     .reply_markup(markup_edit_bot()).await?;
@@ -349,6 +784,33 @@ async fn on_callback(bot: WBot, dialogue: MyDialogue, callback: CallbackQuery, d
                 _ => {}
             }
         },
+        BlacklistAdd => if can_edit_blacklist(&db, dialogue.user_id()).await {
+            dialogue.update(State::WaitBlacklistUser).await?;
+            bot.edit_message_text(dialogue.chat_id(), message_id, "Пересылай сообщение от пользователя или присылай его id").await?;
+        },
+        BlacklistSearch => if can_edit_blacklist(&db, dialogue.user_id()).await {
+            dialogue.update(State::WaitBlacklistSearch).await?;
+            bot.edit_message_text(dialogue.chat_id(), message_id, "Присылай id, имя или часть причины").await?;
+        },
+        BlacklistEntry(user_id) => if can_edit_blacklist(&db, dialogue.user_id()).await {
+            let entry = db.blacklist().get(&UserId(user_id)).ok_or(anyhow!("user {user_id} not found in blacklist"))?;
+            let mut markup = InlineKeyboardMarkup::default();
+            if CONF.is_global_admin(dialogue.user_id()) || entry.added_by == dialogue.user_id() {
+                markup = markup.append_row(vec![
+                    InlineKeyboardButton::callback("Удалить из списка", BlacklistRemove(user_id).to_msg_text().unwrap())
+                ]);
+            }
+            bot.edit_message_text(dialogue.chat_id(), message_id, 
+                format!("{} [{user_id}]\nПричина: {}\nДобавил: {}", entry.name, entry.cause, entry.added_by)
+            ).reply_markup(markup).await?;
+        },
+        BlacklistRemove(user_id) => {
+            let entry = db.blacklist().get(&UserId(user_id)).ok_or(anyhow!("user {user_id} not found in blacklist"))?;
+            if CONF.is_global_admin(dialogue.user_id()) || entry.added_by == dialogue.user_id() {
+                db.remove_from_blacklist(UserId(user_id)).await;
+                bot.edit_message_text(dialogue.chat_id(), message_id, format!("{} удален из черного списка", entry.name)).await?;
+            }
+        },
         callback => {
             bot.edit_message_text(dialogue.chat_id(), message_id, "<Неактуально>").await?;
             bail!("invalid callback on common state: {:?}", callback)
@@ -394,6 +856,13 @@ async fn on_command(cmd: Command, bot: WBot, dialogue: MyDialogue, db: DBStorage
             let markup = InlineKeyboardMarkup::new(buttons);
             bot.send_message(dialogue.chat_id(), "Выбери бота для удаления").reply_markup(markup).await.unwrap();
         }
+        Command::Blacklist => if can_edit_blacklist(&db, dialogue.user_id()).await {
+            let count = db.blacklist().count();
+            bot.send_message(dialogue.chat_id(), format!("В общем черном списке барахолок записей: {count}"))
+                .reply_markup(markup_blacklist()).await?;
+        } else {
+            bot.send_message(dialogue.chat_id(), "Черный список доступен только админу сервиса").await?;
+        },
         Command::PublishInfo(text) => if CONF.is_global_admin(dialogue.user_id()) {
             for user_id in db.all_admins().await {
                 bot.send_message(user_id, &text).await.ok_or_log();
@@ -435,8 +904,12 @@ async fn wait_forward(msg: Message, bot: WBot, dialogue: MyDialogue, token: Stri
                     let channel = chat.id;
                     let admin = msg.from.ok_or(anyhow!("Cannot invoke user for message (admin of bot)"))?;
                     let config = BulletinConfig { token, channel, 
-                        admins: vec![(admin.id, make_username(&admin))], 
-                        banned: vec![], templates: vec![], tags: vec![], flags: 0
+                        admins: vec![(admin.id, make_username(&admin), Role::Owner)], 
+                        banned: vec![], templates: vec![], tags: vec![], flags: 0,
+                        warnings: vec![], warn_limit: 3, ads: vec![], log_chat: None,
+                        blacklist: db.blacklist(), questions: vec![], allowlist: vec![], authors: db.authors(),
+                        subscriptions: vec![], muted_notifications: vec![], saved_searches: vec![], catalog_message: None, comment_mutes: vec![], favorites: vec![],
+                        deals: vec![], reviews: vec![], routes: vec![], join_captchas: vec![],
                     };
                     let id = db.save_config(config.clone()).await?;
                     start_bot(id, config, started_bots, sender);
@@ -85,6 +85,21 @@ pub mod flags {
     pub const ONLY_SUBSCRIBERS: Flags = 0b1;
     pub const APPROVE_SUBSCRIBE: Flags = 0b10;
     pub const WITHOUT_DONATE: Flags = 0b100;
+    pub const WARN_BAN: Flags = 0b1000;
+    pub const AUTO_APPROVE_USERNAME: Flags = 0b10000;
+    pub const AUTO_APPROVE_AUTHORS: Flags = 0b100000;
+    pub const AUTO_APPROVE_ALLOWLIST: Flags = 0b1000000;
+    pub const AUTO_DECLINE_BANNED: Flags = 0b10000000;
+    pub const SPAM_LINKS: Flags = 0b100000000;
+    pub const SPAM_INVITES: Flags = 0b1000000000;
+    pub const SPAM_FORWARDS: Flags = 0b10000000000;
+    pub const SPAM_MENTIONS: Flags = 0b100000000000;
+    pub const SPAM_REPEATS: Flags = 0b1000000000000;
+    pub const SPAM_MUTE: Flags = 0b10000000000000;
+    pub const CAPTCHA: Flags = 0b100000000000000;
+    pub const DAILY_DIGEST: Flags = 0b1000000000000000;
+    pub const WEEKLY_DIGEST: Flags = 0b10000000000000000;
+    pub const CATALOG: Flags = 0b100000000000000000;
 
     pub trait FeatureFlags {
         fn check_flag(&self, flag: Flags) -> bool;
@@ -111,4 +126,65 @@ pub mod flags {
         f.toggle_flag(0b100);
         assert_eq!(0b1100, f);
     }
-}
\ No newline at end of file
+}
+
+pub mod roles {
+    use serde::{Serialize, Deserialize};
+
+    pub type Permissions = i32;
+
+    pub const BAN: Permissions = 0b1;
+    pub const MANAGE_ADMINS: Permissions = 0b10;
+    pub const APPROVE_JOINS: Permissions = 0b100;
+    pub const REMOVE_ADS: Permissions = 0b1000;
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Role {
+        Owner,
+        Admin,
+        Moderator,
+    }
+
+    impl Role {
+        pub const ALL: [Role; 3] = [Role::Owner, Role::Admin, Role::Moderator];
+
+        pub fn permissions(self) -> Permissions {
+            match self {
+                Role::Owner => BAN | MANAGE_ADMINS | APPROVE_JOINS | REMOVE_ADS,
+                Role::Admin => BAN | APPROVE_JOINS | REMOVE_ADS,
+                Role::Moderator => BAN | REMOVE_ADS,
+            }
+        }
+        pub fn can(self, permission: Permissions) -> bool {
+            self.permissions() & permission == permission
+        }
+        pub fn name(self) -> &'static str {
+            match self {
+                Role::Owner => "владелец",
+                Role::Admin => "админ",
+                Role::Moderator => "модератор",
+            }
+        }
+        pub fn code(self) -> i64 {
+            self as i64
+        }
+        pub fn from_code(code: i64) -> Self {
+            match code {
+                0 => Role::Owner,
+                1 => Role::Admin,
+                _ => Role::Moderator,
+            }
+        }
+    }
+
+    #[test]
+    fn test_roles() {
+        assert!(Role::Owner.can(MANAGE_ADMINS | BAN));
+        assert!(!Role::Admin.can(MANAGE_ADMINS));
+        assert!(Role::Moderator.can(REMOVE_ADS));
+        assert!(!Role::Moderator.can(APPROVE_JOINS | BAN));
+        for role in Role::ALL {
+            assert_eq!(role, Role::from_code(role.code()));
+        }
+    }
+}
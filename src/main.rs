@@ -35,6 +35,9 @@ pub struct GlobalConfig {
     ///tip url
     #[arg(long, env="TIP_URL")]
     pub tip_url: String,
+    ///allow bot owners to add users to the global blacklist
+    #[arg(long, env="OWNERS_BLACKLIST")]
+    pub owners_blacklist: bool,
     ///path to db file
     #[arg(long="db", default_value="bulletin-configs.db")]
     db_path: String,
@@ -46,5 +49,6 @@ pub static CONF: GlobalConfig = GlobalConfig {
     token: "test".to_string(),
     admin: 0,
     tip_url: "https://example.com".to_string(),
+    owners_blacklist: false,
     db_path: "test.db".to_string(),
 };
\ No newline at end of file
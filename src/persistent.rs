@@ -1,26 +1,176 @@
 
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use crossbeam::channel::{Sender, TryRecvError, Receiver};
 
 use sqlx::{migrate::Migrator, SqlitePool, Sqlite, ConnectOptions, sqlite::SqliteConnectOptions, Error};
 use teloxide::types::{ChatId, UserId};
 
+use crate::bots::roles::Role;
+
 static MIGRATOR: Migrator = sqlx::migrate!();
 type Conn = sqlx::pool::PoolConnection<Sqlite>;
 
 pub enum DBAction {
     AddListener(i64, Receiver<DBAction>),
-    AddAdmin(i64, String),
+    AddAdmin(i64, String, Role),
     RemoveAdmin(i64),
     SetInfo(BotInfo),
-    Ban{id: i64, name: String, cause: String},
+    Ban{id: i64, name: String, cause: String, until: Option<i64>},
     Unban(i64),
+    ExtendBan{id: i64, until: Option<i64>},
+    Warn{id: i64, cause: String, created: i64},
+    AddAd(PublishedAd),
+    RemoveAd{channel: i64, post: i32},
+    Subscribe{id: i64, tag: String},
+    Unsubscribe{id: i64, tag: String},
+    MuteNotifications{id: i64, muted: bool},
+    AddSearch{id: i64, search: SavedSearch},
+    PauseSearch{id: i64, search_id: i64, paused: bool},
+    DeleteSearch{id: i64, search_id: i64},
+    SetCatalog(Option<i32>),
+    /// `thread: None` - все обсуждения, иначе канал и обсуждение
+    MuteComments{id: i64, thread: Option<(i64, i32)>, muted: bool},
+    AddFavorite{id: i64, channel: i64, post: i32},
+    RemoveFavorite{id: i64, channel: i64, post: i32},
+    /// Убирает объявление из избранного у всех
+    ClearFavorites{channel: i64, post: i32},
+    AddDeal(Deal),
+    AddReview(Review),
+    ConfirmDeal{channel: i64, post: i32},
+    RemoveDeal{channel: i64, post: i32},
+    AddJoinCaptcha{id: i64, chat_id: i64, message: i32},
+    RemoveJoinCaptcha(i64),
 }
 
 #[derive(Debug, Clone)]
 pub struct BanInfo {
     pub name: String,
     pub cause: String,
+    /// Окончание бана, `None` - бессрочный
+    pub until: Option<i64>,
+}
+
+impl BanInfo {
+    pub fn is_active(&self) -> bool {
+        self.until.map(|until|until > chrono::Utc::now().timestamp()).unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub cause: String,
+    pub created: i64,
+}
+
+/// Опубликованное объявление. Первое сообщение публикации (`post`) однозначно определяет ее в канале
+#[derive(Debug, Clone)]
+pub struct PublishedAd {
+    pub channel: ChatId,
+    pub messages: Vec<i32>,
+    pub user_id: UserId,
+    pub target: i64,
+    pub price: i64,
+    pub text: String,
+    pub tags: Vec<String>,
+    pub created: i64,
+}
+
+impl PublishedAd {
+    pub fn post(&self) -> i32 {
+        self.messages.first().copied().unwrap_or_default()
+    }
+}
+
+/// Вопрос анкеты для вступления. Без вариантов ответ принимается текстом
+#[derive(Debug, Clone)]
+pub struct Question {
+    pub text: String,
+    pub options: Vec<String>,
+}
+
+/// Дополнительный канал для объявлений с тегом и/или целью. Без условий правило не создается
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub channel: ChatId,
+    pub tag: Option<String>,
+    /// `Target as i64`, как цель хранится в объявлениях
+    pub target: Option<i64>,
+}
+
+/// Сохраненный поиск: запрос в том же виде, что и в inline-режиме. `id` уникален в пределах пользователя
+#[derive(Debug, Clone)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub query: String,
+    pub paused: bool,
+}
+
+/// Сделка по проданному объявлению. Канал и пост объявления служат ее идентификатором
+#[derive(Debug, Clone)]
+pub struct Deal {
+    pub channel: ChatId,
+    pub post: i32,
+    pub seller: UserId,
+    pub buyer: UserId,
+    pub title: String,
+    /// Покупатель подтвердил сделку, до этого оценки не принимаются
+    pub confirmed: bool,
+}
+
+/// Оценка участника сделки другим участником
+#[derive(Debug, Clone)]
+pub struct Review {
+    pub channel: ChatId,
+    pub post: i32,
+    pub author: UserId,
+    pub target: UserId,
+    /// От 1 до 5
+    pub score: i64,
+    pub text: String,
+    pub created: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlacklistEntry {
+    pub name: String,
+    pub cause: String,
+    pub added_by: UserId,
+}
+
+/// Общий для всех барахолок черный список
+#[derive(Debug, Default)]
+pub struct Blacklist(Mutex<HashMap<UserId, BlacklistEntry>>);
+
+impl Blacklist {
+    pub fn get(&self, user_id: &UserId) -> Option<BlacklistEntry> {
+        self.0.lock().unwrap().get(user_id).cloned()
+    }
+    pub fn count(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+    /// Ищет записи по id, имени или причине
+    pub fn search(&self, query: &str) -> Vec<(UserId, BlacklistEntry)> {
+        let query = query.trim().to_lowercase();
+        self.0.lock().unwrap().iter()
+            .filter(|(id, entry)|{
+                id.0.to_string() == query
+                || entry.name.to_lowercase().contains(&query)
+                || entry.cause.to_lowercase().contains(&query)
+            })
+            .map(|(id, entry)|(*id, entry.clone()))
+            .collect()
+    }
+}
+
+/// Авторы объявлений во всех барахолках
+#[derive(Debug, Default)]
+pub struct Authors(Mutex<HashSet<UserId>>);
+
+impl Authors {
+    pub fn contains(&self, user_id: &UserId) -> bool {
+        self.0.lock().unwrap().contains(user_id)
+    }
 }
 
 pub struct BotInfo {
@@ -39,11 +189,32 @@ impl Default for BotInfo {
 pub struct BulletinConfig {
     pub token: String,
     pub channel: ChatId,
-    pub admins: Vec<(UserId, String)>,
+    pub admins: Vec<(UserId, String, Role)>,
     pub banned: Vec<(UserId, BanInfo)>,
     pub templates: Vec<(usize, String)>,
     pub tags: Vec<String>,
     pub flags: i32,
+    pub warnings: Vec<(UserId, Warning)>,
+    pub warn_limit: usize,
+    pub ads: Vec<PublishedAd>,
+    pub log_chat: Option<ChatId>,
+    pub blacklist: Arc<Blacklist>,
+    pub questions: Vec<Question>,
+    pub allowlist: Vec<(UserId, String)>,
+    pub authors: Arc<Authors>,
+    pub subscriptions: Vec<(UserId, String)>,
+    pub muted_notifications: Vec<UserId>,
+    pub saved_searches: Vec<(UserId, SavedSearch)>,
+    /// Закрепленное в канале сообщение с каталогом
+    pub catalog_message: Option<i32>,
+    pub comment_mutes: Vec<(UserId, Option<(ChatId, i32)>)>,
+    pub favorites: Vec<(UserId, ChatId, i32)>,
+    pub deals: Vec<Deal>,
+    pub reviews: Vec<Review>,
+    /// Правила распределения объявлений по каналам, по порядку проверки. Не подошло ни одно - объявление идет в `channel`
+    pub routes: Vec<Route>,
+    /// Капчи по заявкам на вступление, оставшиеся без ответа до перезапуска: пользователь, чат и сообщение с капчей
+    pub join_captchas: Vec<(UserId, ChatId, i32)>,
 }
 
 pub async fn worker() -> (Sender<DBAction>, Vec<(i64,BulletinConfig)>, Arc<Storage>) {
@@ -76,11 +247,32 @@ pub async fn worker() -> (Sender<DBAction>, Vec<(i64,BulletinConfig)>, Arc<Stora
                         use DBAction::*;
                         match action {
                             AddListener(..) => log::error!("Unexpected add listener"),
-                            AddAdmin(admin_id, username) => storage.add_admin(*bot_id, admin_id, username).await,
+                            AddAdmin(admin_id, username, role) => storage.add_admin(*bot_id, admin_id, username, role).await,
                             RemoveAdmin(admin_id) => storage.remove_admin(*bot_id, admin_id).await,
                             SetInfo(bot_info) => storage.set_info(*bot_id, bot_info).await,
-                            Ban { id, name , cause} => storage.ban(*bot_id, id, name, cause).await,
+                            Ban { id, name , cause, until } => storage.ban(*bot_id, id, name, cause, until).await,
                             Unban(id) => storage.unban(*bot_id, id).await,
+                            ExtendBan { id, until } => storage.extend_ban(*bot_id, id, until).await,
+                            Warn { id, cause, created } => storage.warn(*bot_id, id, cause, created).await,
+                            AddAd(ad) => storage.add_ad(*bot_id, ad).await,
+                            RemoveAd { channel, post } => storage.remove_ad(*bot_id, channel, post).await,
+                            Subscribe { id, tag } => storage.subscribe(*bot_id, id, tag).await,
+                            Unsubscribe { id, tag } => storage.unsubscribe(*bot_id, id, tag).await,
+                            MuteNotifications { id, muted } => storage.mute_notifications(*bot_id, id, muted).await,
+                            AddSearch { id, search } => storage.add_search(*bot_id, id, search).await,
+                            PauseSearch { id, search_id, paused } => storage.pause_search(*bot_id, id, search_id, paused).await,
+                            DeleteSearch { id, search_id } => storage.delete_search(*bot_id, id, search_id).await,
+                            SetCatalog(message) => storage.set_catalog(*bot_id, message).await,
+                            MuteComments { id, thread, muted } => storage.mute_comments(*bot_id, id, thread, muted).await,
+                            AddFavorite { id, channel, post } => storage.add_favorite(*bot_id, id, channel, post).await,
+                            RemoveFavorite { id, channel, post } => storage.remove_favorite(*bot_id, Some(id), channel, post).await,
+                            ClearFavorites { channel, post } => storage.remove_favorite(*bot_id, None, channel, post).await,
+                            AddDeal(deal) => storage.add_deal(*bot_id, deal).await,
+                            AddReview(review) => storage.add_review(*bot_id, review).await,
+                            ConfirmDeal { channel, post } => storage.confirm_deal(*bot_id, channel, post).await,
+                            RemoveDeal { channel, post } => storage.remove_deal(*bot_id, channel, post).await,
+                            AddJoinCaptcha { id, chat_id, message } => storage.add_join_captcha(*bot_id, id, chat_id, message).await,
+                            RemoveJoinCaptcha(id) => storage.remove_join_captcha(*bot_id, id).await,
                         };
                     }
                     Err(TryRecvError::Disconnected) => del_indexes.push(i),
@@ -105,11 +297,36 @@ async fn make_pool() -> SqlitePool {
     pool
 }
 
-pub struct Storage(SqlitePool);
+pub struct Storage(SqlitePool, Arc<Blacklist>, Arc<Authors>);
 
 impl Storage {
     async fn new() -> Arc<Self> {
-        Arc::new(Self(make_pool().await))
+        let pool = make_pool().await;
+        let blacklist = get_blacklist(&mut pool.acquire().await.unwrap()).await;
+        let authors = get_authors(&mut pool.acquire().await.unwrap()).await;
+        Arc::new(Self(pool, Arc::new(Blacklist(Mutex::new(blacklist))), Arc::new(Authors(Mutex::new(authors)))))
+    }
+    pub fn blacklist(&self) -> Arc<Blacklist> {
+        self.1.clone()
+    }
+    pub fn authors(&self) -> Arc<Authors> {
+        self.2.clone()
+    }
+    pub async fn add_to_blacklist(&self, user_id: UserId, entry: BlacklistEntry) {
+        let id = user_id.0 as i64;
+        let added_by = entry.added_by.0 as i64;
+        let created = chrono::Utc::now().timestamp();
+        sqlx::query!(
+            "insert or replace into blacklist (user_id, name, cause, added_by, created_at) values (?1, ?2, ?3, ?4, ?5)",
+            id, entry.name, entry.cause, added_by, created
+        ).execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+        self.1.0.lock().unwrap().insert(user_id, entry);
+    }
+    pub async fn remove_from_blacklist(&self, user_id: UserId) {
+        let id = user_id.0 as i64;
+        sqlx::query!("delete from blacklist where user_id = ?1", id)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+        self.1.0.lock().unwrap().remove(&user_id);
     }
     pub async fn close(&self) {
         log::info!("closing database connections...");
@@ -124,8 +341,8 @@ impl Storage {
         .execute(&mut conn)
         .await?
         .last_insert_rowid();
-        for (admin_id, name) in cfg.admins {
-            self.add_admin(bot_id, admin_id.0 as i64, name).await;
+        for (admin_id, name, role) in cfg.admins {
+            self.add_admin(bot_id, admin_id.0 as i64, name, role).await;
         }
         Ok(bot_id)
     }
@@ -139,35 +356,190 @@ impl Storage {
             let templates = get_templates(&mut conn, id).await;
             let tags = get_tags(&mut conn, id).await;
             let banned = get_banned(&mut conn, id).await;
+            let warnings = get_warnings(&mut conn, id).await;
+            let ads = get_ads(&mut conn, id).await;
+            let questions = get_questions(&mut conn, id).await.into_iter().map(|(_, q)|q).collect();
+            let allowlist = get_allowlist(&mut conn, id).await;
+            let subscriptions = get_subscriptions(&mut conn, id).await;
+            let muted_notifications = get_muted_notifications(&mut conn, id).await;
+            let saved_searches = get_saved_searches(&mut conn, id).await;
+            let comment_mutes = get_comment_mutes(&mut conn, id).await;
+            let favorites = get_favorites(&mut conn, id).await;
+            let deals = get_deals(&mut conn, id).await;
+            let reviews = get_reviews(&mut conn, id).await;
+            let routes = get_routes(&mut conn, id).await.into_iter().map(|(_, route)|route).collect();
+            let join_captchas = get_join_captchas(&mut conn, id).await;
             let conf = BulletinConfig{
                 token: r.token, 
                 channel: ChatId(r.channel), 
-                admins, banned, templates, tags, 
+                admins, banned, templates, tags, warnings, ads,
                 flags: r.flags as i32,
+                warn_limit: r.warn_limit as usize,
+                log_chat: r.log_chat.map(ChatId),
+                blacklist: self.blacklist(),
+                questions,
+                allowlist,
+                authors: self.authors(),
+                subscriptions,
+                muted_notifications,
+                saved_searches,
+                catalog_message: r.catalog_message.map(|id|id as i32),
+                comment_mutes,
+                favorites,
+                deals,
+                reviews,
+                routes,
+                join_captchas,
             };
             res.push((id,conf));
         }
         res
     }
-    async fn add_admin(&self, bot_id: i64, admin_id: i64, username: String) {
+    pub async fn add_admin(&self, bot_id: i64, admin_id: i64, username: String, role: Role) {
         let mut conn = self.0.acquire().await.unwrap();
-        sqlx::query!("insert into bot_admins values (?1, ?2, ?3)", bot_id, admin_id, username)
+        let role = role.code();
+        sqlx::query!("insert into bot_admins (bot_id, user, username, role) values (?1, ?2, ?3, ?4)", bot_id, admin_id, username, role)
         .execute(&mut conn).await.unwrap();
     }
-    async fn remove_admin(&self, bot_id: i64, admin_id: i64) {
+    pub async fn remove_admin(&self, bot_id: i64, admin_id: i64) {
         let mut conn = self.0.acquire().await.unwrap();
         sqlx::query!("delete from bot_admins where bot_id = ?1 and user = ?2", bot_id, admin_id)
         .execute(&mut conn).await.unwrap();
     }
-    async fn ban(&self, bot_id: i64, user_id: i64, name: String, cause: String) {
+    async fn ban(&self, bot_id: i64, user_id: i64, name: String, cause: String, until: Option<i64>) {
         let mut conn = self.0.acquire().await.unwrap();
-        sqlx::query!("insert into banned (bot_id, user_id, name, cause) values (?1, ?2, ?3, ?4)", bot_id, user_id, name, cause)
+        sqlx::query!("insert or replace into banned (bot_id, user_id, name, cause, until) values (?1, ?2, ?3, ?4, ?5)", bot_id, user_id, name, cause, until)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn extend_ban(&self, bot_id: i64, user_id: i64, until: Option<i64>) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("update banned set until=?3 where bot_id=?1 and user_id=?2", bot_id, user_id, until)
             .execute(&mut conn).await.unwrap();
     }
     async fn unban(&self, bot_id: i64, user_id: i64) {
         let mut conn = self.0.acquire().await.unwrap();
         sqlx::query!("delete from banned where bot_id=?1 and user_id=?2", bot_id, user_id)
             .execute(&mut conn).await.unwrap();
+        sqlx::query!("delete from warnings where bot_id=?1 and user_id=?2", bot_id, user_id)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn warn(&self, bot_id: i64, user_id: i64, cause: String, created: i64) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("insert into warnings (bot_id, user_id, cause, created_at) values (?1, ?2, ?3, ?4)", bot_id, user_id, cause, created)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn add_ad(&self, bot_id: i64, ad: PublishedAd) {
+        let mut conn = self.0.acquire().await.unwrap();
+        let post = ad.post();
+        let PublishedAd { channel, messages, user_id, target, price, text, tags, created } = ad;
+        self.2.0.lock().unwrap().insert(user_id);
+        let channel = channel.0;
+        let user_id = user_id.0 as i64;
+        sqlx::query!("insert or ignore into authors (user_id) values (?1)", user_id)
+            .execute(&mut conn).await.unwrap();
+        let messages = messages.iter().map(|id|id.to_string()).collect::<Vec<_>>().join(",");
+        let tags = tags.join("\n");
+        sqlx::query!(
+            "insert into ads (bot_id, channel, post, messages, user_id, target, price, text, tags, created_at) 
+            values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            bot_id, channel, post, messages, user_id, target, price, text, tags, created
+        ).execute(&mut conn).await.unwrap();
+    }
+    async fn remove_ad(&self, bot_id: i64, channel: i64, post: i32) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("delete from ads where bot_id=?1 and channel=?2 and post=?3", bot_id, channel, post)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn subscribe(&self, bot_id: i64, user_id: i64, tag: String) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("insert or ignore into subscriptions (bot_id, user_id, tag) values (?1, ?2, ?3)", bot_id, user_id, tag)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn unsubscribe(&self, bot_id: i64, user_id: i64, tag: String) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("delete from subscriptions where bot_id=?1 and user_id=?2 and tag=?3", bot_id, user_id, tag)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn mute_notifications(&self, bot_id: i64, user_id: i64, muted: bool) {
+        let mut conn = self.0.acquire().await.unwrap();
+        if muted {
+            sqlx::query!("insert or ignore into muted_notifications (bot_id, user_id) values (?1, ?2)", bot_id, user_id)
+                .execute(&mut conn).await.unwrap();
+        } else {
+            sqlx::query!("delete from muted_notifications where bot_id=?1 and user_id=?2", bot_id, user_id)
+                .execute(&mut conn).await.unwrap();
+        }
+    }
+    async fn add_search(&self, bot_id: i64, user_id: i64, search: SavedSearch) {
+        let mut conn = self.0.acquire().await.unwrap();
+        let SavedSearch { id, query, paused } = search;
+        sqlx::query!("insert into saved_searches (bot_id, user_id, id, query, paused) values (?1, ?2, ?3, ?4, ?5)", bot_id, user_id, id, query, paused)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn pause_search(&self, bot_id: i64, user_id: i64, id: i64, paused: bool) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("update saved_searches set paused=?4 where bot_id=?1 and user_id=?2 and id=?3", bot_id, user_id, id, paused)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn delete_search(&self, bot_id: i64, user_id: i64, id: i64) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("delete from saved_searches where bot_id=?1 and user_id=?2 and id=?3", bot_id, user_id, id)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn set_catalog(&self, bot_id: i64, message: Option<i32>) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("update bots set catalog_message = ?1 where id = ?2", message, bot_id)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn mute_comments(&self, bot_id: i64, user_id: i64, thread: Option<(i64, i32)>, muted: bool) {
+        let mut conn = self.0.acquire().await.unwrap();
+        let (channel, thread) = thread.unwrap_or_default();
+        if muted {
+            sqlx::query!("insert or ignore into comment_mutes (bot_id, user_id, channel, thread) values (?1, ?2, ?3, ?4)", bot_id, user_id, channel, thread)
+                .execute(&mut conn).await.unwrap();
+        } else {
+            sqlx::query!("delete from comment_mutes where bot_id=?1 and user_id=?2 and channel=?3 and thread=?4", bot_id, user_id, channel, thread)
+                .execute(&mut conn).await.unwrap();
+        }
+    }
+    async fn add_favorite(&self, bot_id: i64, user_id: i64, channel: i64, post: i32) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("insert or ignore into favorites (bot_id, user_id, channel, post) values (?1, ?2, ?3, ?4)", bot_id, user_id, channel, post)
+            .execute(&mut conn).await.unwrap();
+    }
+    /// Без `user_id` - у всех пользователей
+    async fn remove_favorite(&self, bot_id: i64, user_id: Option<i64>, channel: i64, post: i32) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("delete from favorites where bot_id=?1 and (?2 is null or user_id=?2) and channel=?3 and post=?4", bot_id, user_id, channel, post)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn add_deal(&self, bot_id: i64, deal: Deal) {
+        let mut conn = self.0.acquire().await.unwrap();
+        let Deal { channel, post, seller, buyer, title, confirmed } = deal;
+        let (channel, seller, buyer) = (channel.0, seller.0 as i64, buyer.0 as i64);
+        sqlx::query!(
+            "insert or replace into deals (bot_id, channel, post, seller, buyer, title, confirmed) values (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            bot_id, channel, post, seller, buyer, title, confirmed
+        ).execute(&mut conn).await.unwrap();
+    }
+    async fn confirm_deal(&self, bot_id: i64, channel: i64, post: i32) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("update deals set confirmed = 1 where bot_id=?1 and channel=?2 and post=?3", bot_id, channel, post)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn remove_deal(&self, bot_id: i64, channel: i64, post: i32) {
+        let mut conn = self.0.acquire().await.unwrap();
+        sqlx::query!("delete from deals where bot_id=?1 and channel=?2 and post=?3", bot_id, channel, post)
+            .execute(&mut conn).await.unwrap();
+    }
+    async fn add_review(&self, bot_id: i64, review: Review) {
+        let mut conn = self.0.acquire().await.unwrap();
+        let Review { channel, post, author, target, score, text, created } = review;
+        let (channel, author, target) = (channel.0, author.0 as i64, target.0 as i64);
+        sqlx::query!(
+            "insert or ignore into reviews (bot_id, channel, post, author, target, score, text, created_at) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            bot_id, channel, post, author, target, score, text, created
+        ).execute(&mut conn).await.unwrap();
     }
     pub async fn get_info(&self, bot_id: i64) -> Option<BotInfo> {
         let mut conn = self.0.acquire().await.unwrap();
@@ -182,23 +554,39 @@ impl Storage {
             bot_id, username, channel_name, invite_link
         ).execute(&mut conn).await.unwrap();
     } 
+    /// Боты, которыми пользователь управляет в father-боте. Админы и модераторы
+    /// работают только в самом боте, настройки доступны владельцам
     pub async fn get_bots(&self, admin_id: i64) -> Vec<(i64, String)> {
         let mut conn = self.0.acquire().await.unwrap();
+        let owner = Role::Owner.code();
         sqlx::query!(
-            "select i.bot_id, i.username from bot_info as i join bot_admins as a on i.bot_id=a.bot_id where a.user=?1",
-            admin_id
+            "select i.bot_id, i.username from bot_info as i join bot_admins as a on i.bot_id=a.bot_id where a.user=?1 and a.role=?2",
+            admin_id, owner
         ).fetch_all(&mut conn).await.unwrap().into_iter().map(|r|(r.bot_id, r.username)).collect()
     }
     pub async fn get_config(&self, bot_id: i64) -> Option<BulletinConfig> {
         let mut conn = self.0.acquire().await.unwrap();
         let bot = sqlx::query!(
-            "select token, channel, flags from bots where id=?1",
+            "select token, channel, flags, warn_limit, log_chat, catalog_message from bots where id=?1",
             bot_id
         ).fetch_optional(&mut conn).await.unwrap()?;
         let admins = get_admins(&mut conn, bot_id).await;
         let templates = get_templates(&mut conn, bot_id).await;
         let tags = get_tags(&mut conn, bot_id).await;
         let banned = get_banned(&mut conn, bot_id).await;
+        let warnings = get_warnings(&mut conn, bot_id).await;
+        let ads = get_ads(&mut conn, bot_id).await;
+        let questions = get_questions(&mut conn, bot_id).await.into_iter().map(|(_, q)|q).collect();
+        let allowlist = get_allowlist(&mut conn, bot_id).await;
+        let subscriptions = get_subscriptions(&mut conn, bot_id).await;
+        let muted_notifications = get_muted_notifications(&mut conn, bot_id).await;
+        let saved_searches = get_saved_searches(&mut conn, bot_id).await;
+        let comment_mutes = get_comment_mutes(&mut conn, bot_id).await;
+        let favorites = get_favorites(&mut conn, bot_id).await;
+        let deals = get_deals(&mut conn, bot_id).await;
+        let reviews = get_reviews(&mut conn, bot_id).await;
+        let routes = get_routes(&mut conn, bot_id).await.into_iter().map(|(_, route)|route).collect();
+        let join_captchas = get_join_captchas(&mut conn, bot_id).await;
 
         let config = BulletinConfig {
             token: bot.token, 
@@ -208,6 +596,24 @@ impl Storage {
             templates,
             tags,
             flags: bot.flags as i32,
+            warnings,
+            warn_limit: bot.warn_limit as usize,
+            ads,
+            log_chat: bot.log_chat.map(ChatId),
+            blacklist: self.blacklist(),
+            questions,
+            allowlist,
+            authors: self.authors(),
+            subscriptions,
+            muted_notifications,
+            saved_searches,
+            catalog_message: bot.catalog_message.map(|id|id as i32),
+            comment_mutes,
+            favorites,
+            deals,
+            reviews,
+            routes,
+            join_captchas,
         };
         Some(config)
     }
@@ -244,6 +650,52 @@ impl Storage {
     pub async fn get_tags(&self, bot_id: i64) -> Vec<String> {
         get_tags(&mut self.0.acquire().await.unwrap(), bot_id).await
     }
+    pub async fn add_question(&self, bot_id: i64, question: Question) {
+        let options = question.options.join("\n");
+        sqlx::query!("insert into questions (bot_id, text, options) values (?1, ?2, ?3)", bot_id, question.text, options)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+    }
+    pub async fn delete_question(&self, bot_id: i64, id: i64) {
+        sqlx::query!("delete from questions where bot_id = ?1 and id = ?2", bot_id, id)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+    }
+    pub async fn add_to_allowlist(&self, bot_id: i64, user_id: i64, name: String) {
+        sqlx::query!("insert or replace into allowlist (bot_id, user_id, name) values (?1, ?2, ?3)", bot_id, user_id, name)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+    }
+    pub async fn remove_from_allowlist(&self, bot_id: i64, user_id: i64) {
+        sqlx::query!("delete from allowlist where bot_id = ?1 and user_id = ?2", bot_id, user_id)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+    }
+    pub async fn get_allowlist(&self, bot_id: i64) -> Vec<(UserId, String)> {
+        get_allowlist(&mut self.0.acquire().await.unwrap(), bot_id).await
+    }
+    pub async fn add_route(&self, bot_id: i64, route: Route) {
+        let channel = route.channel.0;
+        sqlx::query!("insert into routes (bot_id, channel, tag, target) values (?1, ?2, ?3, ?4)", bot_id, channel, route.tag, route.target)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+    }
+    pub async fn delete_route(&self, bot_id: i64, id: i64) {
+        sqlx::query!("delete from routes where bot_id = ?1 and id = ?2", bot_id, id)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+    }
+    async fn add_join_captcha(&self, bot_id: i64, user_id: i64, chat_id: i64, message: i32) {
+        sqlx::query!("insert or replace into join_captchas (bot_id, user_id, chat_id, message) values (?1, ?2, ?3, ?4)", bot_id, user_id, chat_id, message)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+    }
+    async fn remove_join_captcha(&self, bot_id: i64, user_id: i64) {
+        sqlx::query!("delete from join_captchas where bot_id = ?1 and user_id = ?2", bot_id, user_id)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+    }
+    pub async fn get_routes(&self, bot_id: i64) -> Vec<(i64, Route)> {
+        get_routes(&mut self.0.acquire().await.unwrap(), bot_id).await
+    }
+    pub async fn get_questions(&self, bot_id: i64) -> Vec<(i64, Question)> {
+        get_questions(&mut self.0.acquire().await.unwrap(), bot_id).await
+    }
+    pub async fn get_admins(&self, bot_id: i64) -> Vec<(UserId, String, Role)> {
+        get_admins(&mut self.0.acquire().await.unwrap(), bot_id).await
+    }
     pub async fn all_admins(&self) -> Vec<UserId> {
         sqlx::query!("select distinct user from bot_admins")
             .fetch_all(&mut self.0.acquire().await.unwrap()).await.unwrap()
@@ -254,6 +706,16 @@ impl Storage {
         sqlx::query!("update bots set flags = ?1 where id = ?2", flags, bot_id)
             .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
     }
+    pub async fn update_log_chat(&self, bot_id: i64, log_chat: Option<ChatId>) {
+        let log_chat = log_chat.map(|chat|chat.0);
+        sqlx::query!("update bots set log_chat = ?1 where id = ?2", log_chat, bot_id)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+    }
+    pub async fn update_warn_limit(&self, bot_id: i64, limit: usize) {
+        let limit = limit as i64;
+        sqlx::query!("update bots set warn_limit = ?1 where id = ?2", limit, bot_id)
+            .execute(&mut self.0.acquire().await.unwrap()).await.unwrap();
+    }
 }
 
 
@@ -263,17 +725,17 @@ async fn get_templates(conn: &mut Conn, bot_id: i64) -> Vec<(usize, String)> {
         .map(|r|(r.text_id as usize, r.text)).collect()
 }
 
-async fn get_admins(conn: &mut Conn, bot_id: i64) -> Vec<(UserId, String)> {
-    sqlx::query!("select user, username from bot_admins where bot_id=?1", bot_id)
+async fn get_admins(conn: &mut Conn, bot_id: i64) -> Vec<(UserId, String, Role)> {
+    sqlx::query!("select user, username, role from bot_admins where bot_id=?1", bot_id)
         .fetch_all(conn).await.unwrap()
-        .into_iter().map(|r|(UserId(r.user as u64), r.username))
+        .into_iter().map(|r|(UserId(r.user as u64), r.username, Role::from_code(r.role)))
         .collect()
 }
 
 async fn get_banned(conn: &mut Conn, bot_id: i64) -> Vec<(UserId, BanInfo)> {
-    sqlx::query!("select user_id, name, cause from banned where bot_id=?1", bot_id)
+    sqlx::query!("select user_id, name, cause, until from banned where bot_id=?1", bot_id)
         .fetch_all(conn).await.unwrap()
-        .into_iter().map(|r|(UserId(r.user_id as u64), BanInfo{name: r.name, cause: r.cause}))
+        .into_iter().map(|r|(UserId(r.user_id as u64), BanInfo{name: r.name, cause: r.cause, until: r.until}))
         .collect()
 }
 
@@ -283,3 +745,141 @@ async fn get_tags(conn: &mut Conn, bot_id: i64) -> Vec<String> {
         .into_iter().map(|r|r.name)
         .collect()
 }
+
+async fn get_questions(conn: &mut Conn, bot_id: i64) -> Vec<(i64, Question)> {
+    sqlx::query!("select id, text, options from questions where bot_id = ?1 order by id", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|(r.id, Question {
+            text: r.text,
+            options: r.options.split('\n').filter(|o|!o.is_empty()).map(ToOwned::to_owned).collect(),
+        }))
+        .collect()
+}
+
+async fn get_join_captchas(conn: &mut Conn, bot_id: i64) -> Vec<(UserId, ChatId, i32)> {
+    sqlx::query!("select user_id, chat_id, message from join_captchas where bot_id = ?1", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|(UserId(r.user_id as u64), ChatId(r.chat_id), r.message as i32))
+        .collect()
+}
+
+async fn get_routes(conn: &mut Conn, bot_id: i64) -> Vec<(i64, Route)> {
+    sqlx::query!("select id, channel, tag, target from routes where bot_id = ?1 order by id", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|(r.id, Route {
+            channel: ChatId(r.channel),
+            tag: r.tag,
+            target: r.target,
+        }))
+        .collect()
+}
+
+async fn get_warnings(conn: &mut Conn, bot_id: i64) -> Vec<(UserId, Warning)> {
+    sqlx::query!("select user_id, cause, created_at from warnings where bot_id = ?1", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|(UserId(r.user_id as u64), Warning{cause: r.cause, created: r.created_at}))
+        .collect()
+}
+
+async fn get_ads(conn: &mut Conn, bot_id: i64) -> Vec<PublishedAd> {
+    sqlx::query!(
+        "select channel, messages, user_id, target, price, text, tags, created_at from ads where bot_id = ?1 order by created_at", 
+        bot_id
+    ).fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|PublishedAd {
+            channel: ChatId(r.channel),
+            messages: r.messages.split(',').filter_map(|id|id.parse().ok()).collect(),
+            user_id: UserId(r.user_id as u64),
+            target: r.target,
+            price: r.price,
+            text: r.text,
+            tags: r.tags.split('\n').filter(|t|!t.is_empty()).map(ToOwned::to_owned).collect(),
+            created: r.created_at,
+        })
+        .collect()
+}
+
+async fn get_allowlist(conn: &mut Conn, bot_id: i64) -> Vec<(UserId, String)> {
+    sqlx::query!("select user_id, name from allowlist where bot_id = ?1", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|(UserId(r.user_id as u64), r.name))
+        .collect()
+}
+
+async fn get_subscriptions(conn: &mut Conn, bot_id: i64) -> Vec<(UserId, String)> {
+    sqlx::query!("select user_id, tag from subscriptions where bot_id = ?1", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|(UserId(r.user_id as u64), r.tag))
+        .collect()
+}
+
+async fn get_muted_notifications(conn: &mut Conn, bot_id: i64) -> Vec<UserId> {
+    sqlx::query!("select user_id from muted_notifications where bot_id = ?1", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|UserId(r.user_id as u64))
+        .collect()
+}
+
+async fn get_saved_searches(conn: &mut Conn, bot_id: i64) -> Vec<(UserId, SavedSearch)> {
+    sqlx::query!("select user_id, id, query, paused from saved_searches where bot_id = ?1 order by id", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|(UserId(r.user_id as u64), SavedSearch { id: r.id, query: r.query, paused: r.paused != 0 }))
+        .collect()
+}
+
+async fn get_comment_mutes(conn: &mut Conn, bot_id: i64) -> Vec<(UserId, Option<(ChatId, i32)>)> {
+    sqlx::query!("select user_id, channel, thread from comment_mutes where bot_id = ?1", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|(UserId(r.user_id as u64), (r.thread != 0).then_some((ChatId(r.channel), r.thread as i32))))
+        .collect()
+}
+
+async fn get_favorites(conn: &mut Conn, bot_id: i64) -> Vec<(UserId, ChatId, i32)> {
+    sqlx::query!("select user_id, channel, post from favorites where bot_id = ?1 order by rowid", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|(UserId(r.user_id as u64), ChatId(r.channel), r.post as i32))
+        .collect()
+}
+
+async fn get_deals(conn: &mut Conn, bot_id: i64) -> Vec<Deal> {
+    sqlx::query!("select channel, post, seller, buyer, title, confirmed from deals where bot_id = ?1", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|Deal {
+            channel: ChatId(r.channel),
+            post: r.post as i32,
+            seller: UserId(r.seller as u64),
+            buyer: UserId(r.buyer as u64),
+            title: r.title,
+            confirmed: r.confirmed != 0,
+        })
+        .collect()
+}
+
+async fn get_reviews(conn: &mut Conn, bot_id: i64) -> Vec<Review> {
+    sqlx::query!("select channel, post, author, target, score, text, created_at from reviews where bot_id = ?1 order by created_at", bot_id)
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|Review {
+            channel: ChatId(r.channel),
+            post: r.post as i32,
+            author: UserId(r.author as u64),
+            target: UserId(r.target as u64),
+            score: r.score,
+            text: r.text,
+            created: r.created_at,
+        })
+        .collect()
+}
+
+async fn get_authors(conn: &mut Conn) -> HashSet<UserId> {
+    sqlx::query!("select user_id from authors")
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|UserId(r.user_id as u64))
+        .collect()
+}
+
+async fn get_blacklist(conn: &mut Conn) -> HashMap<UserId, BlacklistEntry> {
+    sqlx::query!("select user_id, name, cause, added_by from blacklist")
+        .fetch_all(conn).await.unwrap()
+        .into_iter().map(|r|(UserId(r.user_id as u64), BlacklistEntry{name: r.name, cause: r.cause, added_by: UserId(r.added_by as u64)}))
+        .collect()
+}